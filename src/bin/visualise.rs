@@ -0,0 +1,25 @@
+use std::{env, fs, process};
+
+use advent_of_code_2024::visualiser;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let [_, name, path] = &args[..] else {
+        eprintln!("usage: visualise <day6|day14|day15|day15-wide|day16|day18> <input file>");
+        process::exit(2);
+    };
+
+    let input = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("cannot read {}: {}", path, err);
+        process::exit(1);
+    });
+    let Some(simulation) = visualiser::simulation(name, input.trim_end()) else {
+        eprintln!("no simulation for {}", name);
+        process::exit(2);
+    };
+
+    if let Err(err) = visualiser::play(simulation) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use crate::list_stats::ListComparison;
 
 #[aoc_generator(day1)]
 pub fn input_generator(input: &str) -> (Vec<u32>, Vec<u32>) {
@@ -14,28 +14,17 @@ pub fn input_generator(input: &str) -> (Vec<u32>, Vec<u32>) {
 }
 
 #[aoc(day1, part1)]
-pub fn part1(input: &(Vec<u32>, Vec<u32>)) -> u32 {
-    let (mut a, mut b) = input.clone();
-    a.sort();
-    b.sort();
-    std::iter::zip(a.iter(), b.iter()).fold(0, |acc, (a, b)| acc + u32::abs_diff(*a, *b))
+pub fn part1(input: &(Vec<u32>, Vec<u32>)) -> u128 {
+    ListComparison::from_streams(input.0.iter().copied(), input.1.iter().copied())
+        .sorted_distance()
+        .unwrap()
 }
 
 #[aoc(day1, part2)]
-pub fn part2(input: &(Vec<u32>, Vec<u32>)) -> u32 {
-    let map_a = get_number_counts(&input.0);
-    let map_b = get_number_counts(&input.1);
-
-    map_a.iter().fold(0, |acc, (k, v)| {
-        acc + map_b.get(k).map_or(0, |v2| k * v * v2)
-    })
-}
-
-fn get_number_counts(input: &Vec<u32>) -> HashMap<u32, u32> {
-    input.iter().fold(HashMap::new(), |mut acc, &x| {
-        *acc.entry(x).or_insert(0) += 1;
-        acc
-    })
+pub fn part2(input: &(Vec<u32>, Vec<u32>)) -> i128 {
+    ListComparison::from_streams(input.0.iter().copied(), input.1.iter().copied())
+        .similarity()
+        .unwrap()
 }
 
 #[cfg(test)]
@@ -1,106 +1,172 @@
-use std::{collections::HashSet, iter::repeat_n};
+use std::{collections::BTreeSet, num::NonZeroU8};
 
 use itertools::Itertools;
 
-type TopographyMap = Vec<Vec<i8>>;
+use crate::utils::{Direction, Pos};
 
-#[aoc_generator(day10)]
-fn input_generator(input: &str) -> TopographyMap {
-    input
-        .lines()
-        .map(|line| line.chars().map(|c| (c as i32 - 0x30) as i8).collect())
-        .collect()
+const PEAK: u8 = 9;
+
+pub struct TopographyMap {
+    heights: Vec<Vec<Option<u8>>>,
 }
 
-#[aoc(day10, part1)]
-fn part1(input: &TopographyMap) -> usize {
-    let mut visited_points = HashSet::new();
-
-    (0..input.len())
-        .cartesian_product(0..input[0].len())
-        .filter(|&(i, j)| input[i][j] == 0)
-        .map(|(i, j)| {
-            visited_points.clear();
-            calculate_score_1(input, i, j, &mut visited_points)
-        })
-        .sum()
+impl From<&str> for TopographyMap {
+    fn from(input: &str) -> Self {
+        let heights = input
+            .lines()
+            .map(|line| {
+                line.chars()
+                    .map(|c| c.to_digit(10).map(|h| h as u8))
+                    .collect()
+            })
+            .collect();
+        TopographyMap { heights }
+    }
 }
 
-fn calculate_score_1(
-    topography_map: &TopographyMap,
-    i: usize,
-    j: usize,
-    visited_points: &mut HashSet<[usize; 2]>,
-) -> usize {
-    if visited_points.contains(&[i, j]) {
-        return 0;
-    }
-    visited_points.insert([i, j]);
-
-    if topography_map[i][j] == 9 {
-        1
-    } else {
-        [(-1, 0), (0, -1), (1, 0), (0, 1)]
-            .iter()
-            .map(|(di, dj)| (i as isize + di, j as isize + dj))
-            .filter(|(ni, _)| *ni >= 0)
-            .filter(|(_, nj)| *nj >= 0)
-            .filter(|(ni, _)| *ni < topography_map.len() as isize)
-            .filter(|(_, nj)| *nj < topography_map[i].len() as isize)
-            .filter(|&(ni, nj)| {
-                topography_map[ni as usize][nj as usize]
-                    .overflowing_sub(topography_map[i][j])
-                    .0
-                    == 1
+// Every climb must gain height, otherwise trails could loop between equal heights.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClimbRule {
+    Step(NonZeroU8),
+    UpTo(u8),
+}
+
+impl ClimbRule {
+    fn allows(self, from: u8, to: u8) -> bool {
+        match self {
+            ClimbRule::Step(step) => to.checked_sub(from) == Some(step.get()),
+            ClimbRule::UpTo(max) => to > from && to - from <= max,
+        }
+    }
+}
+
+impl Default for ClimbRule {
+    fn default() -> Self {
+        ClimbRule::Step(NonZeroU8::MIN)
+    }
+}
+
+impl TopographyMap {
+    fn height(&self, pos: Pos) -> Option<u8> {
+        *self
+            .heights
+            .get(usize::try_from(pos.y).ok()?)?
+            .get(usize::try_from(pos.x).ok()?)?
+    }
+
+    fn positions(&self) -> impl Iterator<Item = Pos> + '_ {
+        (0..self.heights.len()).flat_map(move |y| {
+            (0..self.heights[y].len()).map(move |x| Pos {
+                x: x as i64,
+                y: y as i64,
             })
-            .map(|(ni, nj)| {
-                calculate_score_1(topography_map, ni as usize, nj as usize, visited_points)
+        })
+    }
+
+    pub fn trailheads(&self) -> Vec<Pos> {
+        self.positions()
+            .filter(|&pos| self.height(pos) == Some(0))
+            .collect()
+    }
+
+    fn climbs(&self, pos: Pos, rule: ClimbRule) -> impl Iterator<Item = Pos> + '_ {
+        let height = self.height(pos);
+        Direction::into_iter()
+            .map(move |dir| pos + dir)
+            .filter(move |&next| {
+                height
+                    .zip(self.height(next))
+                    .is_some_and(|(from, to)| rule.allows(from, to))
             })
-            .sum()
+    }
+
+    pub fn reachable_peaks(&self, trailhead: Pos, rule: ClimbRule) -> BTreeSet<Pos> {
+        let mut visited = BTreeSet::from([trailhead]);
+        let mut stack = vec![trailhead];
+
+        while let Some(pos) = stack.pop() {
+            for next in self.climbs(pos, rule) {
+                if visited.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+
+        visited
+            .into_iter()
+            .filter(|&pos| self.height(pos) == Some(PEAK))
+            .collect()
+    }
+
+    // Number of distinct trails from every cell to any peak, filled from the peaks downwards so
+    // cells without trails are computed once like any other.
+    pub fn ratings(&self, rule: ClimbRule) -> Vec<Vec<u64>> {
+        let mut ratings = self
+            .heights
+            .iter()
+            .map(|row| vec![0; row.len()])
+            .collect_vec();
+
+        for pos in self
+            .positions()
+            .filter(|&pos| self.height(pos).is_some())
+            .sorted_by_key(|&pos| std::cmp::Reverse(self.height(pos)))
+        {
+            ratings[pos] = if self.height(pos) == Some(PEAK) {
+                1
+            } else {
+                self.climbs(pos, rule).map(|next| ratings[next]).sum()
+            };
+        }
+
+        ratings
+    }
+
+    pub fn trails(&self, trailhead: Pos, rule: ClimbRule) -> Vec<Vec<Pos>> {
+        let mut trails = Vec::new();
+        let mut path = vec![trailhead];
+        self.extend_trails(&mut path, rule, &mut trails);
+        trails
+    }
+
+    fn extend_trails(&self, path: &mut Vec<Pos>, rule: ClimbRule, trails: &mut Vec<Vec<Pos>>) {
+        let pos = *path.last().unwrap();
+        if self.height(pos) == Some(PEAK) {
+            trails.push(path.clone());
+            return;
+        }
+
+        for next in self.climbs(pos, rule) {
+            path.push(next);
+            self.extend_trails(path, rule, trails);
+            path.pop();
+        }
     }
 }
 
-#[aoc(day10, part2)]
-fn part2(input: &TopographyMap) -> usize {
-    let mut scores = repeat_n(repeat_n(0, input[0].len()).collect_vec(), input.len()).collect_vec();
+#[aoc_generator(day10)]
+fn input_generator(input: &str) -> TopographyMap {
+    TopographyMap::from(input)
+}
 
-    (0..input.len())
-        .cartesian_product(0..input[0].len())
-        .filter(|&(i, j)| input[i][j] == 0)
-        .map(|(i, j)| calculate_score_2(input, i, j, &mut scores))
+#[aoc(day10, part1)]
+fn part1(input: &TopographyMap) -> usize {
+    input
+        .trailheads()
+        .into_iter()
+        .map(|trailhead| input.reachable_peaks(trailhead, ClimbRule::default()).len())
         .sum()
 }
 
-fn calculate_score_2(
-    topography_map: &TopographyMap,
-    i: usize,
-    j: usize,
-    scores: &mut Vec<Vec<usize>>,
-) -> usize {
-    if scores[i][j] != 0 {
-        return scores[i][j];
-    } else if topography_map[i][j] == 9 {
-        scores[i][j] = 1;
-        return 1;
-    }
-
-    scores[i][j] = [(-1, 0), (0, -1), (1, 0), (0, 1)]
-        .iter()
-        .map(|(di, dj)| (i as isize + di, j as isize + dj))
-        .filter(|(ni, _)| *ni >= 0)
-        .filter(|(_, nj)| *nj >= 0)
-        .filter(|(ni, _)| *ni < topography_map.len() as isize)
-        .filter(|(_, nj)| *nj < topography_map[i].len() as isize)
-        .filter(|&(ni, nj)| {
-            topography_map[ni as usize][nj as usize]
-                .overflowing_sub(topography_map[i][j])
-                .0
-                == 1
-        })
-        .map(|(ni, nj)| calculate_score_2(topography_map, ni as usize, nj as usize, scores))
-        .sum();
+#[aoc(day10, part2)]
+fn part2(input: &TopographyMap) -> u64 {
+    let ratings = input.ratings(ClimbRule::default());
 
-    scores[i][j]
+    input
+        .trailheads()
+        .into_iter()
+        .map(|trailhead| ratings[trailhead])
+        .sum()
 }
 
 #[cfg(test)]
@@ -134,4 +200,51 @@ mod tests {
             13
         );
     }
+
+    #[test]
+    fn test_impassable_cells() {
+        let map = input_generator("...0...\n...1...\n...2...\n6543456\n7.....7\n8.....8\n9.....9");
+        assert_eq!(part1(&map), 2);
+        assert_eq!(part2(&map), 2);
+    }
+
+    #[test]
+    fn test_trails() {
+        let map = input_generator("0123\n1234\n2345\n3456\n456.\n5678\n6789");
+        let trailhead = Pos { x: 0, y: 0 };
+        let trails = map.trails(trailhead, ClimbRule::default());
+        let ratings = map.ratings(ClimbRule::default());
+
+        assert_eq!(trails.len() as u64, ratings[trailhead]);
+        assert!(trails.iter().all_unique());
+        assert!(trails.iter().all(|trail| trail.len() == 10
+            && trail
+                .iter()
+                .tuple_windows()
+                .all(|(a, b)| a.manhattan_distance(*b) == 1)));
+        assert_eq!(
+            map.reachable_peaks(trailhead, ClimbRule::default()),
+            BTreeSet::from([Pos { x: 3, y: 6 }])
+        );
+    }
+
+    #[test]
+    fn test_climb_rules() {
+        let map = input_generator("0369\n....");
+        let trailhead = Pos { x: 0, y: 0 };
+        assert!(map
+            .trails(trailhead, ClimbRule::Step(NonZeroU8::MIN))
+            .is_empty());
+        assert_eq!(
+            map.trails(trailhead, ClimbRule::Step(NonZeroU8::new(3).unwrap())),
+            vec![(0..4).map(|x| Pos { x, y: 0 }).collect_vec()]
+        );
+        assert_eq!(map.trails(trailhead, ClimbRule::UpTo(2)).len(), 0);
+        assert_eq!(map.ratings(ClimbRule::UpTo(3))[0][0], 1);
+
+        let map = input_generator("08\n19");
+        assert_eq!(map.trails(trailhead, ClimbRule::UpTo(8)).len(), 2);
+        assert_eq!(map.trails(trailhead, ClimbRule::UpTo(7)).len(), 0);
+        assert_eq!(map.ratings(ClimbRule::Step(NonZeroU8::MIN))[0][0], 0);
+    }
 }
@@ -1,121 +1,185 @@
-use std::{collections::HashMap, iter::once};
+use std::{collections::HashMap, iter::once, ops::AddAssign};
+
+use num_bigint::BigUint;
 
 const N1: usize = 25;
 
+pub trait StoneRules {
+    fn blink(&self, stone: u64) -> (u64, Option<u64>);
+}
+
+pub struct DefaultRules;
+
+impl StoneRules for DefaultRules {
+    fn blink(&self, stone: u64) -> (u64, Option<u64>) {
+        if stone == 0 {
+            return (1, None);
+        }
+
+        let n_digits = count_digits(stone);
+        if n_digits % 2 == 1 {
+            (stone * 2024, None)
+        } else {
+            let half = 10u64.pow(n_digits / 2);
+            (stone / half, Some(stone % half))
+        }
+    }
+}
+
+fn count_digits(n: u64) -> u32 {
+    n.checked_ilog10().map_or(1, |log| log + 1)
+}
+
+pub trait Count: Clone + From<u8> + for<'a> AddAssign<&'a Self> {}
+
+impl<C: Clone + From<u8> + for<'a> AddAssign<&'a C>> Count for C {}
+
+fn parse_stones(input: &str) -> Vec<u64> {
+    input
+        .lines()
+        .flat_map(|line| line.split_whitespace())
+        .map(|stone_number| stone_number.parse::<u64>().unwrap())
+        .collect()
+}
+
 #[aoc(day11, part1, fold)]
 fn part1(input: &str) -> usize {
-    let iter: Box<dyn Iterator<Item = usize>> = Box::new(
-        input
-            .lines()
-            .flat_map(|line| line.split_whitespace())
-            .map(|stone_number| stone_number.parse::<usize>().unwrap()),
-    );
+    let iter: Box<dyn Iterator<Item = u64>> = Box::new(parse_stones(input).into_iter());
 
     (0..N1)
         .fold(iter, |iter, _| Box::new(part1_step(iter)))
         .count()
 }
 
-fn part1_step(iter: impl Iterator<Item = usize>) -> impl Iterator<Item = usize> {
-    iter.flat_map(|stone_number| match stone_number {
-        0 => once(Some(1)).chain(once(None)),
-        stone => {
-            let n_digits = (stone as f64).log10().floor() as usize + 1;
-            if n_digits % 2 == 1 {
-                once(Some(stone * 2024)).chain(once(None))
-            } else {
-                once(Some(stone / 10usize.pow((n_digits / 2) as u32)))
-                    .chain(once(Some(stone % 10usize.pow((n_digits / 2) as u32))))
-            }
-        }
+fn part1_step(iter: impl Iterator<Item = u64>) -> impl Iterator<Item = u64> {
+    iter.flat_map(|stone| {
+        let (first, second) = DefaultRules.blink(stone);
+        once(first).chain(second)
     })
-    .flatten()
 }
 
 #[aoc(day11, part1, recursive)]
 fn part1_alternative(input: &str) -> usize {
-    input
-        .lines()
-        .flat_map(|line| line.split_whitespace())
-        .map(|stone_number| stone_number.parse::<usize>().unwrap())
-        .map(|stone| part1_process_stone(stone, 25))
+    parse_stones(input)
+        .into_iter()
+        .map(|stone| part1_process_stone(stone, N1))
         .sum()
 }
 
-fn part1_process_stone(stone: usize, n_steps: usize) -> usize {
+fn part1_process_stone(stone: u64, n_steps: usize) -> usize {
     if n_steps == 0 {
         1
     } else {
-        let n = n_steps - 1;
-        if stone == 0 {
-            part1_process_stone(1, n)
-        } else {
-            let n_digits = (stone as f64).log10().floor() as usize + 1;
-
-            if n_digits % 2 == 1 {
-                part1_process_stone(stone * 2024, n)
-            } else {
-                let n_half = n_digits / 2;
-                let first_half = stone / 10usize.pow(n_half as u32);
-                let second_half = stone % 10usize.pow(n_half as u32);
-                part1_process_stone(first_half, n) + part1_process_stone(second_half, n)
+        match DefaultRules.blink(stone) {
+            (first, None) => part1_process_stone(first, n_steps - 1),
+            (first, Some(second)) => {
+                part1_process_stone(first, n_steps - 1) + part1_process_stone(second, n_steps - 1)
             }
         }
     }
 }
+
 const N2: usize = 75;
 
-type Cache = HashMap<[usize; 2], usize>;
+type Cache = HashMap<(u64, usize), u128>;
 
 #[aoc(day11, part1, tree)]
-fn tree_solution(input: &str) -> usize {
+fn tree_solution(input: &str) -> u128 {
     tree_cached_solution(input, N1)
 }
 
 #[aoc(day11, part2, tree)]
-fn part2(input: &str) -> usize {
+fn part2(input: &str) -> u128 {
     tree_cached_solution(input, N2)
 }
 
-fn tree_cached_solution(input: &str, n: usize) -> usize {
+#[aoc(day11, part2, frequencies)]
+fn part2_frequencies(input: &str) -> u128 {
+    count_stones(&DefaultRules, &parse_stones(input), N2)
+}
+
+fn tree_cached_solution(input: &str, n: usize) -> u128 {
     let mut cache: Cache = HashMap::new();
 
-    input
-        .lines()
-        .flat_map(|line| line.split_whitespace())
-        .map(|stone_number| stone_number.parse::<usize>().unwrap())
-        .map(|stone| part2_process_stone(stone, n, &mut cache))
+    parse_stones(input)
+        .into_iter()
+        .map(|stone| part2_process_stone(&DefaultRules, stone, n, &mut cache))
         .sum()
 }
 
-fn part2_process_stone(stone: usize, n: usize, cache: &mut Cache) -> usize {
-    let key = [stone, n];
-    if !cache.contains_key(&[stone, n]) {
-        if n == 0 {
-            cache.insert(key, 1);
-        } else {
-            let n = n - 1;
-            if stone == 0 {
-                let result = part2_process_stone(1, n, cache);
-                cache.insert(key, result);
-            } else {
-                let n_digits = (stone as f64).log10().floor() as usize + 1;
-
-                if n_digits % 2 == 1 {
-                    let result = part2_process_stone(stone * 2024, n, cache);
-                    cache.insert(key, result);
-                } else {
-                    let n_half = n_digits / 2;
-                    let first_half = stone / 10usize.pow(n_half as u32);
-                    let second_half = stone % 10usize.pow(n_half as u32);
-                    let first_half_result = part2_process_stone(first_half, n, cache);
-                    let second_half_result = part2_process_stone(second_half, n, cache);
-                    cache.insert(key, first_half_result + second_half_result);
-                }
-            }
+fn part2_process_stone(rules: &impl StoneRules, stone: u64, n: usize, cache: &mut Cache) -> u128 {
+    if n == 0 {
+        return 1;
+    }
+    if let Some(&result) = cache.get(&(stone, n)) {
+        return result;
+    }
+
+    let result = match rules.blink(stone) {
+        (first, None) => part2_process_stone(rules, first, n - 1, cache),
+        (first, Some(second)) => {
+            part2_process_stone(rules, first, n - 1, cache)
+                + part2_process_stone(rules, second, n - 1, cache)
+        }
+    };
+    cache.insert((stone, n), result);
+    result
+}
+
+fn blink_frequencies<C: Count>(
+    rules: &impl StoneRules,
+    counts: &HashMap<u64, C>,
+) -> HashMap<u64, C> {
+    let mut next = HashMap::with_capacity(counts.len());
+    let mut add = |stone: u64, count: &C| {
+        *next.entry(stone).or_insert_with(|| C::from(0)) += count;
+    };
+
+    for (&stone, count) in counts {
+        let (first, second) = rules.blink(stone);
+        add(first, count);
+        if let Some(second) = second {
+            add(second, count);
         }
     }
-    cache[&key]
+
+    next
+}
+
+pub fn generations<'r, C: Count + 'r>(
+    rules: &'r impl StoneRules,
+    stones: &[u64],
+) -> impl Iterator<Item = HashMap<u64, C>> + 'r {
+    let mut initial = HashMap::new();
+    for &stone in stones {
+        *initial.entry(stone).or_insert_with(|| C::from(0)) += &C::from(1);
+    }
+
+    std::iter::successors(Some(initial), move |counts| {
+        Some(blink_frequencies(rules, counts))
+    })
+}
+
+pub fn count_stones<C: Count>(rules: &impl StoneRules, stones: &[u64], blinks: usize) -> C {
+    generations::<C>(rules, stones)
+        .nth(blinks)
+        .unwrap()
+        .values()
+        .fold(C::from(0), |mut total, count| {
+            total += count;
+            total
+        })
+}
+
+pub fn distinct_stones_per_blink(
+    rules: &impl StoneRules,
+    stones: &[u64],
+    blinks: usize,
+) -> Vec<usize> {
+    generations::<BigUint>(rules, stones)
+        .take(blinks + 1)
+        .map(|counts| counts.len())
+        .collect()
 }
 
 #[cfg(test)]
@@ -136,13 +200,52 @@ mod tests {
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(EXAMPLE_INPUT), 55312);
+        assert_eq!(part2(EXAMPLE_INPUT), 65601038650482);
+        assert_eq!(part2_frequencies(EXAMPLE_INPUT), 65601038650482);
     }
 
     #[test]
     fn num_of_digits() {
-        assert_eq!((3 as f64).log10().floor() as usize + 1, 1);
-        assert_eq!((10 as f64).log10().floor() as usize + 1, 2);
-        assert_eq!((100 as f64).log10().floor() as usize + 1, 3);
+        assert_eq!(count_digits(0), 1);
+        assert_eq!(count_digits(3), 1);
+        assert_eq!(count_digits(10), 2);
+        assert_eq!(count_digits(100), 3);
+        assert_eq!(count_digits(999_999_999_999_999_999), 18);
+        assert_eq!(count_digits(u64::MAX), 20);
+    }
+
+    #[test]
+    fn distinct_stones() {
+        assert_eq!(
+            distinct_stones_per_blink(&DefaultRules, &[125, 17], 6),
+            vec![2, 3, 4, 5, 8, 12, 15]
+        );
+    }
+
+    #[test]
+    fn thousand_blinks() {
+        let count: BigUint = count_stones(&DefaultRules, &[125, 17], 1000);
+        assert!(count.bits() > 128);
+        assert_eq!(
+            count_stones::<BigUint>(&DefaultRules, &[125, 17], 75),
+            BigUint::from(65601038650482u64)
+        );
+    }
+
+    struct DoubleRules;
+
+    impl StoneRules for DoubleRules {
+        fn blink(&self, stone: u64) -> (u64, Option<u64>) {
+            (stone, Some(stone + 1))
+        }
+    }
+
+    #[test]
+    fn custom_rules() {
+        assert_eq!(count_stones::<u128>(&DoubleRules, &[0], 100), 1 << 100);
+        assert_eq!(
+            distinct_stones_per_blink(&DoubleRules, &[0], 3),
+            vec![1, 2, 3, 4]
+        );
     }
 }
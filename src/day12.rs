@@ -1,140 +1,362 @@
+use std::collections::HashSet;
+
 use itertools::Itertools;
 
+use crate::utils::{Direction, Pos};
+
 const CARDINAL_DIRECTIONS: [[isize; 2]; 4] = [[-1, 0], [0, -1], [1, 0], [0, 1]];
+const ALL_DIRECTIONS: [[isize; 2]; 8] = [
+    [-1, -1],
+    [0, -1],
+    [1, -1],
+    [-1, 0],
+    [1, 0],
+    [-1, 1],
+    [0, 1],
+    [1, 1],
+];
 
-#[aoc(day12, part1)]
-fn part1(input: &str) -> u64 {
-    let mut map = input
-        .lines()
-        .map(|line| line.chars().map(Some).collect_vec())
-        .collect_vec();
-    let mut res = 0;
-
-    for i in 0..map.len() {
-        for j in 0..map[i].len() {
-            if let Some(region) = map[i][j] {
-                res += crawl_region(&mut map, i, j, region);
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub plant: char,
+    pub area: u64,
+    pub perimeter: u64,
+    pub sides: u64,
+    // Inclusive corners of the bounding box.
+    pub min: Pos,
+    pub max: Pos,
+    // Every hole is listed as the ids of the regions filling it.
+    pub holes: Vec<Vec<usize>>,
+}
+
+impl Region {
+    pub fn price(&self) -> u64 {
+        self.area * self.perimeter
+    }
+
+    pub fn discounted_price(&self) -> u64 {
+        self.area * self.sides
+    }
+}
+
+// Closed polygons through cell corners. The outer ring runs clockwise on screen (y down) and the
+// hole rings counter-clockwise, so the region is always on the right.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outline {
+    pub outer: Vec<Pos>,
+    pub holes: Vec<Vec<Pos>>,
+}
+
+impl Outline {
+    pub fn vertex_count(&self) -> usize {
+        self.outer.len() + self.holes.iter().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn to_svg_path(&self) -> String {
+        std::iter::once(&self.outer)
+            .chain(&self.holes)
+            .map(|ring| {
+                let points = ring.iter().map(|pos| format!("{} {}", pos.x, pos.y));
+                format!("M {} Z", points.format(" L "))
+            })
+            .join(" ")
+    }
+}
+
+fn signed_area(ring: &[Pos]) -> i64 {
+    ring.iter()
+        .circular_tuple_windows()
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum()
+}
+
+pub struct Regions {
+    width: usize,
+    height: usize,
+    plants: Vec<char>,
+    labels: Vec<usize>,
+    regions: Vec<Region>,
+}
+
+impl From<&str> for Regions {
+    fn from(input: &str) -> Self {
+        let grid = input
+            .lines()
+            .map(|line| line.chars().collect_vec())
+            .collect_vec();
+        let height = grid.len();
+        let width = grid.first().map_or(0, Vec::len);
+
+        let mut regions = Regions {
+            width,
+            height,
+            plants: grid.into_iter().flatten().collect(),
+            labels: vec![usize::MAX; width * height],
+            regions: Vec::new(),
+        };
+        regions.label();
+        regions.find_holes();
+        regions.count_sides();
+        regions
+    }
+}
+
+impl Regions {
+    fn neighbour(&self, idx: usize, [dx, dy]: [isize; 2]) -> Option<usize> {
+        let x = (idx % self.width).checked_add_signed(dx)?;
+        let y = (idx / self.width).checked_add_signed(dy)?;
+        (x < self.width && y < self.height).then_some(y * self.width + x)
+    }
+
+    fn same_plant(&self, idx: usize, dir: [isize; 2]) -> bool {
+        self.neighbour(idx, dir)
+            .is_some_and(|n| self.plants[n] == self.plants[idx])
+    }
+
+    fn pos(&self, idx: usize) -> Pos {
+        Pos {
+            x: (idx % self.width) as i64,
+            y: (idx / self.width) as i64,
+        }
+    }
+
+    fn label(&mut self) {
+        for start in 0..self.plants.len() {
+            if self.labels[start] != usize::MAX {
+                continue;
             }
+
+            let id = self.regions.len();
+            let mut region = Region {
+                plant: self.plants[start],
+                area: 0,
+                perimeter: 0,
+                sides: 0,
+                min: self.pos(start),
+                max: self.pos(start),
+                holes: Vec::new(),
+            };
+            self.labels[start] = id;
+            let mut stack = vec![start];
+
+            while let Some(idx) = stack.pop() {
+                let pos = self.pos(idx);
+                region.area += 1;
+                region.min = Pos {
+                    x: region.min.x.min(pos.x),
+                    y: region.min.y.min(pos.y),
+                };
+                region.max = Pos {
+                    x: region.max.x.max(pos.x),
+                    y: region.max.y.max(pos.y),
+                };
+
+                for dir in CARDINAL_DIRECTIONS {
+                    if !self.same_plant(idx, dir) {
+                        region.perimeter += 1;
+                        continue;
+                    }
+                    let next = self.neighbour(idx, dir).unwrap();
+                    if self.labels[next] == usize::MAX {
+                        self.labels[next] = id;
+                        stack.push(next);
+                    }
+                }
+            }
+
+            self.regions.push(region);
         }
     }
 
-    res
-}
+    // Holes are the 8-connected components of other cells inside the bounding box, padded by one
+    // cell, that cannot reach the padding.
+    fn find_holes(&mut self) {
+        for id in 0..self.regions.len() {
+            let (min, max) = (self.regions[id].min, self.regions[id].max);
+            let width = (max.x - min.x + 3) as usize;
+            let height = (max.y - min.y + 3) as usize;
+            let label_at = |local: usize| {
+                let x = (local % width) as i64 + min.x - 1;
+                let y = (local / width) as i64 + min.y - 1;
+                (x >= 0 && y >= 0 && x < self.width as i64 && y < self.height as i64)
+                    .then(|| self.labels[y as usize * self.width + x as usize])
+            };
+            let local_neighbours = |local: usize| {
+                let (x, y) = (local % width, local / width);
+                ALL_DIRECTIONS.iter().filter_map(move |&[dx, dy]| {
+                    let nx = x.checked_add_signed(dx)?;
+                    let ny = y.checked_add_signed(dy)?;
+                    (nx < width && ny < height).then_some(ny * width + nx)
+                })
+            };
+
+            let mut seen = (0..width * height)
+                .map(|local| label_at(local) == Some(id))
+                .collect_vec();
+            let fill = |start: usize, seen: &mut Vec<bool>| {
+                let mut component = Vec::new();
+                let mut stack = vec![start];
+                seen[start] = true;
+                while let Some(local) = stack.pop() {
+                    component.extend(label_at(local));
+                    for next in local_neighbours(local) {
+                        if !seen[next] {
+                            seen[next] = true;
+                            stack.push(next);
+                        }
+                    }
+                }
+                component
+            };
 
-fn crawl_region(map: &mut Vec<Vec<Option<char>>>, i: usize, j: usize, region: char) -> u64 {
-    let mut stack = vec![(i, j)];
-    let mut visited = vec![(i, j)];
-    let mut area = 0;
-    let mut perimeter = 0;
-
-    while let Some((i, j)) = stack.pop() {
-        area += 1;
-        for &[di, dj] in CARDINAL_DIRECTIONS.iter() {
-            let ni = i as isize + di;
-            let nj = j as isize + dj;
-
-            if !(ni >= 0
-                && nj >= 0
-                && ni < map.len() as isize
-                && nj < map[i].len() as isize
-                && map[ni as usize][nj as usize] == Some(region))
-            {
-                perimeter += 1;
-            } else {
-                if !visited.contains(&(ni as usize, nj as usize)) {
-                    stack.push((ni as usize, nj as usize));
-                    visited.push((ni as usize, nj as usize));
+            fill(0, &mut seen);
+            let mut holes = Vec::new();
+            for local in 0..width * height {
+                if !seen[local] {
+                    holes.push(
+                        fill(local, &mut seen)
+                            .into_iter()
+                            .sorted()
+                            .dedup()
+                            .collect(),
+                    );
                 }
             }
+            self.regions[id].holes = holes;
         }
     }
 
-    for (i, j) in visited {
-        map[i][j] = None;
+    pub fn iter(&self) -> impl Iterator<Item = &Region> {
+        self.regions.iter()
     }
 
-    area * perimeter
-}
+    pub fn get(&self, id: usize) -> &Region {
+        &self.regions[id]
+    }
 
-#[aoc(day12, part2)]
-fn part2(input: &str) -> u64 {
-    let mut map = input
-        .lines()
-        .map(|line| line.chars().map(Some).collect_vec())
-        .collect_vec();
-    let mut res = 0;
-
-    for i in 0..map.len() {
-        for j in 0..map[i].len() {
-            if let Some(region) = map[i][j] {
-                res += crawl_region_with_discount(&mut map, i, j, region);
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn label_at(&self, pos: Pos) -> usize {
+        self.labels[pos.y as usize * self.width + pos.x as usize]
+    }
+
+    // Unit boundary edges as (start corner, direction), in row-major cell order.
+    fn boundary_edges(&self, id: usize) -> Vec<(Pos, Direction)> {
+        let (min, max) = (self.regions[id].min, self.regions[id].max);
+        let mut edges = Vec::new();
+
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let idx = y as usize * self.width + x as usize;
+                if self.labels[idx] != id {
+                    continue;
+                }
+                let corner = |dx, dy| Pos {
+                    x: x + dx,
+                    y: y + dy,
+                };
+                let sides = [
+                    ([0, -1], corner(0, 0), Direction::Right),
+                    ([1, 0], corner(1, 0), Direction::Down),
+                    ([0, 1], corner(1, 1), Direction::Left),
+                    ([-1, 0], corner(0, 1), Direction::Up),
+                ];
+                for (dir, start, heading) in sides {
+                    if !self.same_plant(idx, dir) {
+                        edges.push((start, heading));
+                    }
+                }
             }
         }
+
+        edges
     }
 
-    res
-}
+    // Every corner of the outer outline and of the hole outlines starts a new side.
+    fn count_sides(&mut self) {
+        for id in 0..self.regions.len() {
+            self.regions[id].sides = self.outline(id).vertex_count() as u64;
+        }
+    }
 
-fn crawl_region_with_discount(
-    map: &mut Vec<Vec<Option<char>>>,
-    i: usize,
-    j: usize,
-    region: char,
-) -> u64 {
-    let mut stack = vec![(i, j)];
-    let mut visited = vec![(i, j)];
-    let mut area = 0;
-    let mut vertices = 0;
-
-    while let Some((i, j)) = stack.pop() {
-        area += 1;
-
-        let all_neighbours = [-1, 0, 1]
-            .iter()
-            .flat_map(|&dj| {
-                [-1, 0, 1]
-                    .iter()
-                    .map(move |&di| (i as isize + di, j as isize + dj))
-            })
-            .map(|(ni, nj)| {
-                if ni >= 0 && nj >= 0 && ni < map.len() as isize && nj < map[i].len() as isize {
-                    map[ni as usize][nj as usize]
-                } else {
-                    None
-                }
-            })
-            .collect_vec();
+    // Where two cells of the region only touch diagonally the trace turns right, keeping the
+    // rings around 8-connected holes in one piece like `find_holes` does.
+    pub fn outline(&self, id: usize) -> Outline {
+        let edges = self.boundary_edges(id);
+        let mut remaining = edges.iter().copied().collect::<HashSet<_>>();
+        let mut rings = Vec::new();
 
-        for neighbour_idx in (1..all_neighbours.len()).step_by(2) {
-            let triplet = (match neighbour_idx {
-                1 => [1, 0, 3],
-                3 => [3, 6, 7],
-                5 => [5, 2, 1],
-                7 => [7, 8, 5],
-                _ => panic!("Invalid neighbour index {}", neighbour_idx),
-            })
-            .iter()
-            .map(|&idx| all_neighbours[idx] == Some(region))
-            .collect_vec();
+        for &first in &edges {
+            if !remaining.remove(&first) {
+                continue;
+            }
 
-            match triplet[0..3] {
-                [true, false, true] | [false, _, false] => vertices += 1,
-                _ => {}
+            let mut ring = Vec::new();
+            let (mut corner, mut heading) = first;
+            loop {
+                let next = corner + heading;
+                let Some(turn) = [heading.turn_right(), heading, heading.turn_left()]
+                    .into_iter()
+                    .find(|&turn| remaining.remove(&(next, turn)))
+                else {
+                    break;
+                };
+                if turn != heading {
+                    ring.push(next);
+                }
+                (corner, heading) = (next, turn);
             }
-            let ni = (i as isize + ((neighbour_idx as isize) % 3 - 1)) as usize;
-            let nj = (j as isize + (neighbour_idx as isize) / 3 - 1) as usize;
-            if triplet[0] && ni < map.len() && nj < map[i].len() && !visited.contains(&(ni, nj)) {
-                stack.push((ni, nj));
-                visited.push((ni, nj));
+            if heading != first.1 {
+                ring.insert(0, first.0);
             }
+
+            rings.push(ring);
+        }
+
+        let (outer, holes) = rings
+            .into_iter()
+            .partition::<Vec<_>, _>(|ring| signed_area(ring) > 0);
+        Outline {
+            outer: outer.into_iter().next().unwrap_or_default(),
+            holes,
         }
     }
 
-    for (i, j) in visited {
-        map[i][j] = None;
+    pub fn to_svg(&self) -> String {
+        let paths = (0..self.regions.len()).map(|id| {
+            let hue = (self.regions[id].plant as u32 * 47) % 360;
+            format!(
+                r#"  <path d="{}" fill="hsl({hue}, 60%, 60%)" fill-rule="evenodd" stroke="black" stroke-width="0.05"/>"#,
+                self.outline(id).to_svg_path()
+            )
+        });
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\">\n{}\n</svg>\n",
+            self.width,
+            self.height,
+            paths.format("\n")
+        )
     }
+}
+
+#[aoc(day12, part1)]
+fn part1(input: &str) -> u64 {
+    Regions::from(input).iter().map(Region::price).sum()
+}
 
-    area * vertices
+#[aoc(day12, part2)]
+fn part2(input: &str) -> u64 {
+    Regions::from(input)
+        .iter()
+        .map(Region::discounted_price)
+        .sum()
 }
 
 #[cfg(test)]
@@ -163,4 +385,84 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(EXAMPLE_INPUT), 1206);
     }
+
+    #[test]
+    fn part2_diagonal_example() {
+        assert_eq!(part2("AAAAAA\nAAABBA\nAAABBA\nABBAAA\nABBAAA\nAAAAAA"), 368);
+        assert_eq!(part2("EEEEE\nEXXXX\nEEEEE\nEXXXX\nEEEEE"), 236);
+    }
+
+    #[test]
+    fn test_region_statistics() {
+        let regions = Regions::from(SIMPLE_INPUT);
+        assert_eq!(regions.len(), 5);
+
+        let c = regions.get(regions.label_at(Pos { x: 2, y: 1 }));
+        assert_eq!((c.plant, c.area, c.perimeter, c.sides), ('C', 4, 10, 8));
+        assert_eq!((c.min, c.max), (Pos { x: 2, y: 1 }, Pos { x: 3, y: 3 }));
+        assert!(c.holes.is_empty());
+    }
+
+    #[test]
+    fn test_holes() {
+        let regions = Regions::from("OOOOO\nOXOXO\nOOOOO\nOXOXO\nOOOOO");
+        let o = regions.get(0);
+        assert_eq!((o.area, o.perimeter, o.sides), (21, 36, 20));
+        assert_eq!(o.holes, vec![vec![1], vec![2], vec![3], vec![4]]);
+
+        let regions = Regions::from("AAAA\nABBA\nABCA\nAAAA");
+        assert_eq!(regions.get(0).holes, vec![vec![1, 2]]);
+        assert!(regions.get(1).holes.is_empty());
+
+        let regions = Regions::from("AAA\nA.A\nAA.");
+        assert!(regions.get(0).holes.is_empty());
+    }
+
+    #[test]
+    fn test_outline() {
+        let regions = Regions::from(SIMPLE_INPUT);
+        let c = regions.outline(regions.label_at(Pos { x: 2, y: 1 }));
+        assert_eq!(
+            c.outer,
+            [
+                (2, 1),
+                (3, 1),
+                (3, 2),
+                (4, 2),
+                (4, 4),
+                (3, 4),
+                (3, 3),
+                (2, 3)
+            ]
+            .map(Pos::from)
+        );
+        assert!(c.holes.is_empty());
+        assert_eq!(
+            c.to_svg_path(),
+            "M 2 1 L 3 1 L 3 2 L 4 2 L 4 4 L 3 4 L 3 3 L 2 3 Z"
+        );
+
+        let regions = Regions::from("OOO\nOXO\nOOO");
+        let o = regions.outline(0);
+        assert_eq!(o.outer, [(0, 0), (3, 0), (3, 3), (0, 3)].map(Pos::from));
+        assert_eq!(
+            o.holes,
+            vec![[(2, 1), (1, 1), (1, 2), (2, 2)].map(Pos::from).to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_sides_follow_outline() {
+        let regions = Regions::from("AAAA\nAXAA\nAAXA\nAAAA");
+        let a = regions.get(0);
+        let outline = regions.outline(0);
+        assert_eq!(
+            (outline.outer.len(), outline.holes.len()),
+            (4, a.holes.len())
+        );
+        assert_eq!(a.sides, 12);
+
+        let regions = Regions::from("AB\nBA");
+        assert!(regions.iter().all(|region| region.sides == 4));
+    }
 }
@@ -3,7 +3,7 @@ use std::cmp::min;
 use itertools::Itertools;
 use regex::Regex;
 
-use crate::utils::Pos;
+use crate::{diophantine::cheapest_solution_2d, utils::Pos};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Machine {
@@ -76,36 +76,20 @@ fn part2(input: &[Machine]) -> i64 {
 }
 
 fn calculate_tokens_with_offset(m: &Machine, offset: i64) -> i64 {
-    let n_a_counter = ((m.prize.x + offset) * m.b.y) - ((m.prize.y + offset) * m.b.x);
-
-    let n_a_denominator = (m.a.x * m.b.y) - (m.a.y * m.b.x);
-
-    if n_a_denominator == 0
-        || n_a_counter / n_a_denominator < 0
-        || n_a_counter % n_a_denominator != 0
-    {
-        return 0;
-    }
-
-    let n_a = n_a_counter / n_a_denominator;
-
-    let n_b_counter = (m.prize.x + offset) - (n_a * m.a.x);
-    let n_b_denominator = m.b.x;
-    if n_b_denominator == 0
-        || n_b_counter / n_b_denominator < 0
-        || n_b_counter % n_b_denominator != 0
-    {
-        return 0;
-    }
-
-    let n_b = n_b_counter / n_b_denominator;
-
-    n_a * 3 + n_b
+    let vector = |pos: Pos| [pos.x as i128, pos.y as i128];
+    let prize = [
+        m.prize.x as i128 + offset as i128,
+        m.prize.y as i128 + offset as i128,
+    ];
+
+    cheapest_solution_2d(vector(m.a), vector(m.b), prize, [3, 1])
+        .map_or(0, |(n_a, n_b)| (n_a * 3 + n_b) as i64)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::Lcg;
 
     #[test]
     fn test_input_generator() {
@@ -142,5 +126,58 @@ Prize: X=18641, Y=10279";
     #[test]
     fn part2_example() {
         assert_eq!(part1_exact(&input_generator(EXAMPLE_INPUT)), 480);
+        assert_eq!(part2(&input_generator(EXAMPLE_INPUT)), 875318608908);
+    }
+
+    #[test]
+    fn collinear_machine() {
+        let machine = Machine {
+            a: Pos { x: 4, y: 8 },
+            b: Pos { x: 1, y: 2 },
+            prize: Pos { x: 10, y: 20 },
+        };
+        assert_eq!(calculate_tokens_with_offset(&machine, 0), 8);
+    }
+
+    #[test]
+    fn exact_matches_brute_force() {
+        let mut rng = Lcg::new(12345);
+        let mut random = |max: u64| rng.below(max) as i64;
+
+        for i in 0..500 {
+            let a = Pos {
+                x: random(30) + 1,
+                y: random(30) + 1,
+            };
+            let b = if i % 3 == 0 {
+                Pos {
+                    x: a.x * 2,
+                    y: a.y * 2,
+                }
+            } else {
+                Pos {
+                    x: random(30) + 1,
+                    y: random(30) + 1,
+                }
+            };
+            let (n_a, n_b) = (random(60), random(60));
+            let machine = Machine {
+                prize: a * n_a + b * n_b + Pos { x: 0, y: random(2) },
+                a,
+                b,
+            };
+
+            let brute_force = (0..=100)
+                .map(|n| find_cheapest_path(&machine, n))
+                .filter(|&tokens| tokens > 0)
+                .min()
+                .unwrap_or(0);
+            assert_eq!(
+                calculate_tokens_with_offset(&machine, 0),
+                brute_force,
+                "{:?}",
+                machine
+            );
+        }
     }
 }
@@ -1,9 +1,9 @@
-use std::fs::File;
-use std::io::{self, Write};
-
 use itertools::Itertools;
 
-use crate::utils::Pos;
+use crate::{
+    utils::Pos,
+    visualiser::{Frame, Simulation},
+};
 
 #[derive(Clone)]
 struct Guard {
@@ -98,30 +98,14 @@ fn part1(input: &str) -> u64 {
     get_quadrant_counts(&guards)
 }
 
-fn save_robot_map(guards: &[Guard], id: usize) -> io::Result<()> {
-    let mut map = Vec::with_capacity(H);
-
-    for _ in 0..H {
-        map.push(vec![0; W]);
-    }
+fn robot_map(guards: &[Guard]) -> Vec<Vec<usize>> {
+    let mut map = vec![vec![0; W]; H];
 
     for g in guards {
         map[g.p.y as usize][g.p.x as usize] += 1;
     }
 
-    let mut file = File::create(format!("day14-step{}.txt", id))?;
-
-    for row in map.iter() {
-        for &field in row.iter() {
-            if field == 0 {
-                write!(file, ".")?;
-            } else {
-                write!(file, "{}", field)?;
-            }
-        }
-        writeln!(file, "")?;
-    }
-    Ok(())
+    map
 }
 
 #[aoc(day14, part2)]
@@ -138,13 +122,53 @@ fn part2(input: &str) -> u64 {
         if score < min_score {
             min_score = score;
             min_i = 1 + i as u64;
-            println!("Step: {}, Score: {}", min_i, min_score);
-            save_robot_map(&guards, min_i as usize).unwrap();
         }
     }
     min_i
 }
 
+struct RobotSimulation {
+    guards: Vec<Guard>,
+    step: usize,
+}
+
+pub fn simulation(input: &str) -> impl Simulation {
+    RobotSimulation {
+        guards: input.lines().map(Guard::from).collect(),
+        step: 0,
+    }
+}
+
+impl Simulation for RobotSimulation {
+    fn frame(&self) -> Frame {
+        Frame::from_grid(
+            &robot_map(&self.guards),
+            |&count| match count {
+                0 => '.',
+                1..=9 => char::from_digit(count as u32, 10).unwrap(),
+                _ => '*',
+            },
+            format!(
+                "second {}, safety factor {}",
+                self.step,
+                get_quadrant_counts(&self.guards)
+            ),
+        )
+    }
+
+    // The robots are back in their starting positions after W * H seconds.
+    fn step(&mut self) -> bool {
+        if self.step == W * H {
+            return false;
+        }
+        for g in self.guards.iter_mut() {
+            g.steps_wrapped(1);
+        }
+        self.step += 1;
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,7 +4,10 @@ use std::{
     ops::{Index, IndexMut},
 };
 
-use crate::utils::{Direction, Pos};
+use crate::{
+    utils::{Direction, Pos},
+    visualiser::{Frame, Simulation},
+};
 
 #[derive(PartialEq, Clone, Copy)]
 enum Field {
@@ -152,7 +155,6 @@ fn part1(input: &str) -> u64 {
     let mut wh: Warehouse = lines.by_ref().take_while(|l| !l.is_empty()).collect();
 
     lines.flat_map(|l| l.chars()).for_each(|c| {
-        // println!("{}", wh);
         wh.move_robot(&c.into());
     });
 
@@ -390,6 +392,95 @@ fn part2(input: &str) -> u64 {
     swh.get_box_coordinates()
 }
 
+trait Robot: fmt::Display {
+    fn move_robot(&mut self, dir: &Direction);
+    fn get_box_coordinates(&self) -> u64;
+}
+
+impl Robot for Warehouse {
+    fn move_robot(&mut self, dir: &Direction) {
+        Warehouse::move_robot(self, dir)
+    }
+
+    fn get_box_coordinates(&self) -> u64 {
+        Warehouse::get_box_coordinates(self)
+    }
+}
+
+impl Robot for ScaledWarehouse {
+    fn move_robot(&mut self, dir: &Direction) {
+        ScaledWarehouse::move_robot(self, dir)
+    }
+
+    fn get_box_coordinates(&self) -> u64 {
+        ScaledWarehouse::get_box_coordinates(self)
+    }
+}
+
+struct WarehouseSimulation<R: Robot> {
+    warehouse: R,
+    moves: Vec<Direction>,
+    next_move: usize,
+}
+
+fn parse_simulation(input: &str) -> (Warehouse, Vec<Direction>) {
+    let mut lines = input.lines();
+    let wh: Warehouse = lines.by_ref().take_while(|l| !l.is_empty()).collect();
+    let moves = lines.flat_map(|l| l.chars()).map(Direction::from).collect();
+    (wh, moves)
+}
+
+pub fn simulation(input: &str) -> impl Simulation {
+    let (warehouse, moves) = parse_simulation(input);
+    WarehouseSimulation {
+        warehouse,
+        moves,
+        next_move: 0,
+    }
+}
+
+pub fn wide_simulation(input: &str) -> impl Simulation {
+    let (warehouse, moves) = parse_simulation(input);
+    WarehouseSimulation {
+        warehouse: ScaledWarehouse::from(warehouse),
+        moves,
+        next_move: 0,
+    }
+}
+
+impl<R: Robot> Simulation for WarehouseSimulation<R> {
+    fn frame(&self) -> Frame {
+        let next = self
+            .moves
+            .get(self.next_move)
+            .map_or(' ', |&dir| char::from(dir));
+        Frame {
+            rows: self
+                .warehouse
+                .to_string()
+                .lines()
+                .map(String::from)
+                .collect(),
+            status: format!(
+                "move {}/{} (next '{}'), GPS sum {}",
+                self.next_move,
+                self.moves.len(),
+                next,
+                self.warehouse.get_box_coordinates()
+            ),
+        }
+    }
+
+    fn step(&mut self) -> bool {
+        let Some(dir) = self.moves.get(self.next_move) else {
+            return false;
+        };
+        self.warehouse.move_robot(dir);
+        self.next_move += 1;
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -457,4 +548,18 @@ v^^>>><<^^<>>^v^<v^vv<>v^<<>^<^v^v><^<<<><<^<v><v<>vv>>v><v^<vv<>v^<<^"
             9021
         );
     }
+
+    #[test]
+    fn test_simulation() {
+        let mut simulation = simulation(SIMPLE_INPUT);
+        assert_eq!(simulation.frame().rows[2], "##@.O..#");
+        let mut steps = 0;
+        while simulation.step() {
+            steps += 1;
+        }
+        assert_eq!(steps, 15);
+        let frame = simulation.frame();
+        assert_eq!(frame.rows[1], "#....OO#");
+        assert!(frame.status.ends_with("GPS sum 2028"));
+    }
 }
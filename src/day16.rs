@@ -1,8 +1,12 @@
-use std::{cmp::Ordering, collections::{BinaryHeap, HashMap}};
+use std::collections::{HashMap, HashSet};
 
 use itertools::Itertools;
 
-use crate::utils::{Direction, Pos};
+use crate::{
+    search::Search,
+    utils::{Direction, Pos},
+    visualiser::{Frame, Simulation},
+};
 
 #[derive(PartialEq, Clone, Copy)]
 enum Field {
@@ -59,94 +63,48 @@ fn find_field(labirynth: &Vec<Vec<Field>>, field: Field) -> Pos {
     panic!("Field not found");
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-struct State {
-    cost: u64,
-    pos: Pos,
-    dir: Direction,
+fn get_dist(dist: &HashMap<(Pos, Direction), u64>, pos: Pos, dir: Direction) -> u64 {
+    *dist.get(&(pos, dir)).unwrap_or(&u64::MAX)
 }
 
-impl State {
-    fn neighbours(&self) -> [State; 3] {
-        [
-            State {
-                cost: self.cost + 1,
-                pos: self.pos + &self.dir,
-                dir: self.dir,
-            },
-            State {
-                cost: self.cost + 1000,
-                pos: self.pos,
-                dir: self.dir.turn_left(),
-            },
-            State {
-                cost: self.cost + 1000,
-                pos: self.pos,
-                dir: self.dir.turn_right(),
-            },
-        ]
-    }
+fn end_cost(search: &Search<(Pos, Direction)>, end_pos: Pos) -> Option<u64> {
+    [Direction::Up, Direction::Right]
+        .iter()
+        .filter_map(|&dir| search.distance((end_pos, dir)))
+        .min()
 }
 
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other
-            .cost
-            .cmp(&self.cost)
-            .then_with(|| self.pos.cmp(&other.pos))
+// Settles a single state. Returns false once every state at most as expensive as the best way into
+// the end is settled, as those are all the states the best paths can go through.
+fn step(search: &mut Search<(Pos, Direction)>, labirynth: &[Vec<Field>], end_pos: Pos) -> bool {
+    let Some(((pos, dir), cost)) = search.pop() else {
+        return false;
+    };
+    if end_cost(search, end_pos).is_some_and(|best| cost > best) {
+        return false;
     }
-}
 
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    for ((next_pos, next_dir), next_cost) in [
+        ((pos + dir, dir), cost + 1),
+        ((pos, dir.turn_left()), cost + 1000),
+        ((pos, dir.turn_right()), cost + 1000),
+    ] {
+        if labirynth[next_pos.y as usize][next_pos.x as usize] != Field::Wall {
+            search.relax((next_pos, next_dir), next_cost);
+        }
     }
-}
-
-fn get_dist(dist: &HashMap<(Pos, Direction), u64>, pos: Pos, dir: Direction) -> u64 {
-    *dist.get(&(pos, dir)).unwrap_or(&u64::MAX)
+    true
 }
 
 fn dijkstra(
-    labirynth: &Vec<Vec<Field>>,
+    labirynth: &[Vec<Field>],
     start_pos: Pos,
     start_dir: Direction,
     end_pos: Pos,
-    dist: &mut HashMap<(Pos, Direction), u64>,
-) {
-    let mut heap = BinaryHeap::new();
-
-    dist.insert((start_pos, start_dir), 0);
-
-    heap.push(State {
-        cost: 0,
-        pos: start_pos,
-        dir: start_dir,
-    });
-
-    let mut end_edges_count = (labirynth[end_pos - Direction::Right] == Field::Clear) as u64
-        + (labirynth[end_pos - Direction::Up] == Field::Clear) as u64;
-
-    while let Some(State { cost, pos, dir }) = heap.pop() {
-        if pos == end_pos {
-            end_edges_count -= 1;
-            if end_edges_count == 0 {
-                break;
-            }
-        }
-
-        if cost > get_dist(dist, pos, dir) {
-            continue;
-        }
-
-        for &next in (State { cost, pos, dir }).neighbours().iter() {
-            if labirynth[next.pos] != Field::Wall && next.cost <= get_dist(dist, next.pos, next.dir)
-            {
-                dist.insert((next.pos, next.dir), next.cost);
-                heap.push(next);
-            }
-        }
-    }
+) -> HashMap<(Pos, Direction), u64> {
+    let mut search = Search::new((start_pos, start_dir));
+    while step(&mut search, labirynth, end_pos) {}
+    search.into_distances()
 }
 
 #[aoc(day16, part1)]
@@ -155,9 +113,7 @@ fn part1(labirynth: &Vec<Vec<Field>>) -> u64 {
     let end_pos = find_field(labirynth, Field::End);
     let dir = Direction::Right;
 
-    let mut dist = HashMap::new();
-
-    dijkstra(labirynth, start_pos, dir, end_pos, &mut dist);
+    let dist = dijkstra(labirynth, start_pos, dir, end_pos);
 
     [Direction::Up, Direction::Right]
         .iter()
@@ -172,9 +128,7 @@ fn part2(labirynth: &Vec<Vec<Field>>) -> u64 {
     let end_pos = find_field(labirynth, Field::End);
     let dir = Direction::Right;
 
-    let mut dist = HashMap::new();
-
-    dijkstra(labirynth, start_pos, dir, end_pos, &mut dist);
+    let dist = dijkstra(labirynth, start_pos, dir, end_pos);
 
     let min_dir = [Direction::Up, Direction::Right]
         .iter()
@@ -216,13 +170,6 @@ fn part2(labirynth: &Vec<Vec<Field>>) -> u64 {
         }
     }
 
-    for row in &labirynth {
-        for &field in row {
-            print!("{}", char::from(field));
-        }
-        println!();
-    }
-
     labirynth
         .iter()
         .flatten()
@@ -230,6 +177,85 @@ fn part2(labirynth: &Vec<Vec<Field>>) -> u64 {
         .count() as u64
 }
 
+struct SearchSimulation {
+    labirynth: Vec<Vec<Field>>,
+    search: Search<(Pos, Direction)>,
+    end_pos: Pos,
+    steps: usize,
+}
+
+pub fn simulation(input: &str) -> impl Simulation {
+    let labirynth = input_generator(input);
+    let start_pos = find_field(&labirynth, Field::Start);
+    let end_pos = find_field(&labirynth, Field::End);
+    SearchSimulation {
+        labirynth,
+        search: Search::new((start_pos, Direction::Right)),
+        end_pos,
+        steps: 0,
+    }
+}
+
+impl Simulation for SearchSimulation {
+    fn frame(&self) -> Frame {
+        let visited: HashSet<Pos> = self
+            .search
+            .distances()
+            .keys()
+            .map(|&(pos, _)| pos)
+            .collect();
+        let frontier: HashSet<Pos> = self.search.frontier().map(|(pos, _)| pos).collect();
+        let current = self.search.current().map(|(pos, _)| pos);
+        let mut map = self
+            .labirynth
+            .iter()
+            .map(|row| row.iter().map(|&f| char::from(f)).collect_vec())
+            .collect_vec();
+
+        for (y, row) in map.iter_mut().enumerate() {
+            for (x, c) in row.iter_mut().enumerate() {
+                let pos = Pos {
+                    x: x as i64,
+                    y: y as i64,
+                };
+                if *c != '.' {
+                    continue;
+                }
+                if Some(pos) == current {
+                    *c = '@';
+                } else if frontier.contains(&pos) {
+                    *c = '*';
+                } else if visited.contains(&pos) {
+                    *c = 'o';
+                }
+            }
+        }
+
+        let best = match end_cost(&self.search, self.end_pos) {
+            Some(cost) => cost.to_string(),
+            None => "-".to_string(),
+        };
+        Frame::from_grid(
+            &map,
+            |&c| c,
+            format!(
+                "popped {} states, frontier {}, best score {}",
+                self.steps,
+                self.search.frontier_len(),
+                best
+            ),
+        )
+    }
+
+    fn step(&mut self) -> bool {
+        let running = step(&mut self.search, &self.labirynth, self.end_pos);
+        if running {
+            self.steps += 1;
+        }
+        running
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -295,4 +321,14 @@ mod tests {
             10
         );
     }
+
+    #[test]
+    fn test_simulation() {
+        let mut simulation = simulation(EXAMPLE_INPUT);
+        assert!(simulation.frame().status.ends_with("best score -"));
+        while simulation.step() {}
+        let frame = simulation.frame();
+        assert_eq!(frame.rows.len(), 15);
+        assert!(frame.status.ends_with("best score 7036"));
+    }
 }
@@ -1,11 +1,12 @@
-use std::{
-    cmp::Ordering,
-    collections::{BinaryHeap, HashMap},
-};
+use std::collections::{HashMap, HashSet};
 
 use itertools::{repeat_n, Itertools};
 
-use crate::utils::{Direction, Pos};
+use crate::{
+    search::Search,
+    utils::{Direction, Pos},
+    visualiser::{Frame, Simulation},
+};
 
 fn create_map(size: usize) -> Vec<Vec<char>> {
     (0..size + 2)
@@ -34,60 +35,31 @@ fn fall_bytes(input: &str, memory: &mut Vec<Vec<char>>, n: usize) {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-struct State {
-    cost: u64,
-    pos: Pos,
-}
-
-impl Ord for State {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other
-            .cost
-            .cmp(&self.cost)
-            .then_with(|| self.pos.cmp(&other.pos))
-    }
-}
-
-impl PartialOrd for State {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
 fn get_dist(dist: &HashMap<Pos, u64>, pos: Pos) -> u64 {
     *dist.get(&pos).unwrap_or(&u64::MAX)
 }
 
-fn dijkstra(memory: &Vec<Vec<char>>, start_pos: Pos, end_pos: Pos, dist: &mut HashMap<Pos, u64>) {
-    let mut heap = BinaryHeap::new();
-
-    dist.insert(start_pos, 0);
-
-    heap.push(State {
-        cost: 0,
-        pos: start_pos,
-    });
-
-    while let Some(State { cost, pos }) = heap.pop() {
-        if pos == end_pos {
-            break;
-        }
-
-        if cost > get_dist(dist, pos) {
-            continue;
-        }
+// Settles a single position. Returns false once the exit is reached or nothing is left to visit.
+fn step(search: &mut Search<Pos>, memory: &[Vec<char>], end_pos: Pos) -> bool {
+    let Some((pos, cost)) = search.pop() else {
+        return false;
+    };
+    if pos == end_pos {
+        return false;
+    }
 
-        for next in Direction::into_iter().map(|dir| State {
-            cost: cost + 1,
-            pos: pos + dir,
-        }) {
-            if memory[next.pos] != '#' && next.cost < get_dist(dist, next.pos) {
-                dist.insert(next.pos, next.cost);
-                heap.push(next);
-            }
+    for next in Direction::into_iter().map(|dir| pos + dir) {
+        if memory[next.y as usize][next.x as usize] != '#' {
+            search.relax(next, cost + 1);
         }
     }
+    true
+}
+
+fn dijkstra(memory: &[Vec<char>], start_pos: Pos, end_pos: Pos) -> HashMap<Pos, u64> {
+    let mut search = Search::new(start_pos);
+    while step(&mut search, memory, end_pos) {}
+    search.into_distances()
 }
 
 fn find_path_through_bytes(input: &str, size: usize, n: usize) -> u64 {
@@ -100,15 +72,7 @@ fn find_path_through_bytes(input: &str, size: usize, n: usize) -> u64 {
 
     fall_bytes(input, &mut memory, n);
 
-    // for row in &memory {
-    //     for &field in row {
-    //         print!("{}", field);
-    //     }
-    //     println!();
-    // }
-
-    let mut dist = HashMap::new();
-    dijkstra(&memory, start_pos, end_pos, &mut dist);
+    let dist = dijkstra(&memory, start_pos, end_pos);
 
     get_dist(&dist, end_pos)
 }
@@ -135,15 +99,73 @@ fn part2(input: &str) -> String {
     input.lines().nth(left - 1).unwrap().to_string()
 }
 
+struct SearchSimulation {
+    memory: Vec<Vec<char>>,
+    search: Search<Pos>,
+    end_pos: Pos,
+    steps: usize,
+}
+
+fn byte_simulation(input: &str, size: usize, n: usize) -> SearchSimulation {
+    let mut memory = create_map(size);
+    fall_bytes(input, &mut memory, n);
+    SearchSimulation {
+        memory,
+        search: Search::new(Pos { x: 1, y: 1 }),
+        end_pos: Pos {
+            x: size as i64,
+            y: size as i64,
+        },
+        steps: 0,
+    }
+}
+
+pub fn simulation(input: &str) -> impl Simulation {
+    byte_simulation(input, 71, 1024)
+}
+
+impl Simulation for SearchSimulation {
+    fn frame(&self) -> Frame {
+        let frontier: HashSet<Pos> = self.search.frontier().collect();
+        let mut map = self.memory.clone();
+
+        for &pos in self.search.distances().keys() {
+            map[pos.y as usize][pos.x as usize] = if frontier.contains(&pos) { '*' } else { 'O' };
+        }
+        if let Some(pos) = self.search.current() {
+            map[pos.y as usize][pos.x as usize] = '@';
+        }
+
+        let distance = match self.search.distance(self.end_pos) {
+            Some(cost) => cost.to_string(),
+            None => "-".to_string(),
+        };
+        Frame::from_grid(
+            &map,
+            |&c| c,
+            format!(
+                "popped {} states, frontier {}, distance to exit {}",
+                self.steps,
+                self.search.frontier_len(),
+                distance
+            ),
+        )
+    }
+
+    fn step(&mut self) -> bool {
+        let running = step(&mut self.search, &self.memory, self.end_pos);
+        if running {
+            self.steps += 1;
+        }
+        running
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_part1() {
-        assert_eq!(
-            find_path_through_bytes(
-                "5,4
+    const EXAMPLE_INPUT: &str = "5,4
 4,2
 4,5
 3,0
@@ -167,11 +189,20 @@ mod tests {
 1,0
 0,5
 1,6
-2,0",
-                7,
-                12
-            ),
-            22
-        );
+2,0";
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(find_path_through_bytes(EXAMPLE_INPUT, 7, 12), 22);
+    }
+
+    #[test]
+    fn test_simulation() {
+        let mut simulation = byte_simulation(EXAMPLE_INPUT, 7, 12);
+        assert_eq!(simulation.frame().rows[1], "#*..#...#");
+        while simulation.step() {}
+        let frame = simulation.frame();
+        assert_eq!(frame.rows[7][1..8].to_string(), "#.#OOO@");
+        assert!(frame.status.ends_with("distance to exit 22"));
     }
 }
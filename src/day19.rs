@@ -1,70 +1,163 @@
-use std::collections::HashMap;
-
 use itertools::Itertools;
+use num_bigint::BigUint;
+
+#[derive(Default)]
+struct TrieNode {
+    children: Vec<(u8, usize)>,
+    towel: Option<usize>,
+}
+
+pub struct TowelMatcher<'a> {
+    nodes: Vec<TrieNode>,
+    towels: Vec<&'a str>,
+}
+
+impl<'a> TowelMatcher<'a> {
+    pub fn new(towels: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut matcher = TowelMatcher {
+            nodes: vec![TrieNode::default()],
+            towels: Vec::new(),
+        };
 
-fn does_towel_fit<'a>(
-    design: &'a str,
-    towels: &[&str],
-    cache: &mut HashMap<&'a str, bool>,
-) -> bool {
-    if design.is_empty() {
-        true
-    } else {
-        if cache.contains_key(design) {
-            return *cache.get(design).unwrap();
+        for towel in towels {
+            matcher.insert(towel);
         }
-        let res = towels.iter().any(|towel| {
-            design.starts_with(towel) && does_towel_fit(&design[towel.len()..], towels, cache)
-        });
-        cache.insert(design, res);
-        res
+
+        matcher
     }
-}
 
-#[aoc(day19, part1)]
-fn part1(input: &str) -> u64 {
-    let mut lines_iter = input.lines();
-    let mut cache = HashMap::new();
+    fn insert(&mut self, towel: &'a str) {
+        let mut node = 0;
+        for byte in towel.bytes() {
+            node = match self.child(node, byte) {
+                Some(child) => child,
+                None => {
+                    self.nodes.push(TrieNode::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children.push((byte, child));
+                    child
+                }
+            };
+        }
 
-    let towels = lines_iter.next().unwrap().split(", ").collect_vec();
-    lines_iter.next().unwrap();
+        if self.nodes[node].towel.is_none() {
+            self.nodes[node].towel = Some(self.towels.len());
+            self.towels.push(towel);
+        }
+    }
 
-    lines_iter
-        .filter(|line| does_towel_fit(line, &towels, &mut cache))
-        .count() as u64
-}
+    fn child(&self, node: usize, byte: u8) -> Option<usize> {
+        self.nodes[node]
+            .children
+            .iter()
+            .find(|&&(b, _)| b == byte)
+            .map(|&(_, child)| child)
+    }
+
+    // Lengths of all towels that match the design at the given offset.
+    fn matches_at<'d>(
+        &'d self,
+        design: &'d [u8],
+        start: usize,
+    ) -> impl Iterator<Item = usize> + 'd {
+        design[start..]
+            .iter()
+            .scan(0, |node, &byte| {
+                *node = self.child(*node, byte)?;
+                Some(*node)
+            })
+            .enumerate()
+            .filter(|&(_, node)| self.nodes[node].towel.is_some())
+            .map(|(i, _)| i + 1)
+    }
 
-fn count_arrangements<'a>(
-    design: &'a str,
-    towels: &[&str],
-    cache: &mut HashMap<&'a str, u64>,
-) -> u64 {
-    if design.is_empty() {
-        1
-    } else {
-        if cache.contains_key(design) {
-            return *cache.get(design).unwrap();
+    // The count grows exponentially with the length of the design, past u128 within a few hundred
+    // stripes.
+    pub fn count_arrangements(&self, design: &str) -> BigUint {
+        let design = design.as_bytes();
+        let mut ways = vec![BigUint::ZERO; design.len() + 1];
+        ways[design.len()] = BigUint::from(1u32);
+
+        for start in (0..design.len()).rev() {
+            ways[start] = self
+                .matches_at(design, start)
+                .map(|len| &ways[start + len])
+                .sum();
         }
-        let res = towels
+
+        ways.swap_remove(0)
+    }
+
+    pub fn decompose(&self, design: &'a str) -> Option<Vec<&'a str>> {
+        self.decompose_with(design, |_, _| true)
+    }
+
+    fn decompose_with(
+        &self,
+        design: &'a str,
+        allowed: impl Fn(usize, usize) -> bool,
+    ) -> Option<Vec<&'a str>> {
+        let bytes = design.as_bytes();
+        let mut next_piece = vec![None; bytes.len() + 1];
+        next_piece[bytes.len()] = Some(0);
+
+        for start in (0..bytes.len()).rev() {
+            next_piece[start] = self
+                .matches_at(bytes, start)
+                .find(|&len| allowed(start, len) && next_piece[start + len].is_some());
+        }
+
+        next_piece[0]?;
+
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        while start < bytes.len() {
+            let len = next_piece[start].unwrap();
+            pieces.push(&design[start..start + len]);
+            start += len;
+        }
+
+        Some(pieces)
+    }
+
+    pub fn redundant_towels(&self) -> Vec<&'a str> {
+        self.towels
             .iter()
-            .filter(|&towel| design.starts_with(towel))
-            .map(|towel| count_arrangements(&design[towel.len()..], towels, cache))
-            .sum();
-        cache.insert(design, res);
-        res
+            .copied()
+            .filter(|towel| {
+                self.decompose_with(towel, |start, len| start != 0 || len != towel.len())
+                    .is_some()
+            })
+            .collect()
     }
 }
 
-#[aoc(day19, part2)]
-fn part2(input: &str) -> u64 {
+fn parse(input: &str) -> (TowelMatcher<'_>, Vec<&str>) {
     let mut lines_iter = input.lines();
-    let mut cache = HashMap::new();
 
-    let towels = lines_iter.next().unwrap().split(", ").collect_vec();
+    let matcher = TowelMatcher::new(lines_iter.next().unwrap().split(", "));
     lines_iter.next().unwrap();
 
-    lines_iter
-        .map(|line| count_arrangements(line, &towels, &mut cache))
+    (matcher, lines_iter.collect_vec())
+}
+
+#[aoc(day19, part1)]
+fn part1(input: &str) -> u64 {
+    let (matcher, designs) = parse(input);
+
+    designs
+        .iter()
+        .filter(|design| matcher.decompose(design).is_some())
+        .count() as u64
+}
+
+#[aoc(day19, part2)]
+fn part2(input: &str) -> BigUint {
+    let (matcher, designs) = parse(input);
+
+    designs
+        .iter()
+        .map(|design| matcher.count_arrangements(design))
         .sum()
 }
 
@@ -90,6 +183,33 @@ bbrgwb";
 
     #[test]
     fn test_part2() {
-        assert_eq!(part2(EXAMPLE_INPUT), 16);
+        assert_eq!(part2(EXAMPLE_INPUT), BigUint::from(16u32));
+    }
+
+    #[test]
+    fn test_decompose() {
+        let (matcher, _) = parse(EXAMPLE_INPUT);
+        assert_eq!(matcher.decompose("brwrr"), Some(vec!["b", "r", "wr", "r"]));
+        assert_eq!(matcher.decompose("ubwu"), None);
+    }
+
+    #[test]
+    fn test_redundant_towels() {
+        let (matcher, _) = parse(EXAMPLE_INPUT);
+        assert_eq!(matcher.redundant_towels(), vec!["rb", "gb", "br"]);
+    }
+
+    #[test]
+    fn test_long_design() {
+        let matcher = TowelMatcher::new(["a", "aa"]);
+        let design = "a".repeat(150);
+        assert_eq!(
+            matcher.count_arrangements(&design),
+            BigUint::from(16130531424904581415797907386349u128)
+        );
+
+        let count = |len| matcher.count_arrangements(&"a".repeat(len));
+        assert!(count(200) > BigUint::from(u128::MAX));
+        assert_eq!(count(200), count(199) + count(198));
     }
 }
@@ -1,4 +1,6 @@
-use std::ops::ControlFlow::{self, Break, Continue};
+use std::{cmp::Reverse, ops::Range};
+
+use itertools::Itertools;
 
 type Input = Vec<Vec<u32>>;
 
@@ -24,52 +26,154 @@ pub fn part2(input: &Input) -> u32 {
     safety_check(input, true)
 }
 
-fn safety_check(input: &Input, with_dampener: bool) -> u32 {
-    input.iter().fold(0, |sum, report| {
-        if check_report_monotonous(report, false, with_dampener)
-            || check_report_monotonous(report, true, with_dampener)
-        {
-            sum + 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SafetyRule {
+    pub min_step: u32,
+    pub max_step: u32,
+}
+
+impl Default for SafetyRule {
+    fn default() -> Self {
+        SafetyRule {
+            min_step: 1,
+            max_step: 3,
+        }
+    }
+}
+
+impl SafetyRule {
+    fn allows(&self, prev: u32, next: u32, ascending: bool) -> bool {
+        let step = if ascending {
+            next.checked_sub(prev)
         } else {
-            sum
+            prev.checked_sub(next)
+        };
+        step.is_some_and(|step| (self.min_step..=self.max_step).contains(&step))
+    }
+
+    // The fewest levels to remove, by index, so the report becomes safe.
+    pub fn removals(&self, report: &[u32]) -> Vec<usize> {
+        [true, false]
+            .into_iter()
+            .map(|ascending| self.monotonic_removals(report, ascending))
+            .min_by_key(Vec::len)
+            .unwrap()
+    }
+
+    pub fn min_removals(&self, report: &[u32]) -> usize {
+        self.removals(report).len()
+    }
+
+    pub fn is_safe_with(&self, report: &[u32], removals: usize) -> bool {
+        self.min_removals(report) <= removals
+    }
+
+    // Keeps the longest chain of levels where every step is allowed and removes the rest. The best
+    // chain ending at each distinct level is kept in a max segment tree, so every level finds the
+    // best chain it can extend with one range query, in O(n log n) whatever the step bounds.
+    fn monotonic_removals(&self, report: &[u32], ascending: bool) -> Vec<usize> {
+        let n = report.len();
+        let values = report
+            .iter()
+            .copied()
+            .sorted_unstable()
+            .dedup()
+            .collect_vec();
+        let index_range = |low: u32, high: u32| {
+            values.partition_point(|&v| v < low)..values.partition_point(|&v| v <= high)
+        };
+
+        // Chains are compared by length, preferring the one ending earliest.
+        let mut longest = MaxTree::new(values.len());
+        let mut prev = vec![None; n];
+        for (i, &level) in report.iter().enumerate() {
+            let window = if ascending {
+                level
+                    .checked_sub(self.min_step)
+                    .map(|high| index_range(level.saturating_sub(self.max_step), high))
+            } else {
+                level
+                    .checked_add(self.min_step)
+                    .map(|low| index_range(low, level.saturating_add(self.max_step)))
+            };
+            let before = window.and_then(|range| longest.max(range));
+            debug_assert!(
+                before.is_none_or(|(_, Reverse(j))| self.allows(report[j], level, ascending))
+            );
+
+            prev[i] = before.map(|(_, Reverse(j))| j);
+            let length = before.map_or(1, |(length, _)| length + 1);
+            longest.raise(values.binary_search(&level).unwrap(), (length, Reverse(i)));
         }
-    })
+
+        let mut kept = vec![false; n];
+        let mut current = longest.max(0..values.len()).map(|(_, Reverse(i))| i);
+        while let Some(i) = current {
+            kept[i] = true;
+            current = prev[i];
+        }
+        (0..n).filter(|&i| !kept[i]).collect()
+    }
 }
 
-fn check_report_monotonous(report: &Vec<u32>, ascending: bool, with_dampener: bool) -> bool {
-    let report: Vec<_> = if ascending {
-        report.clone()
-    } else {
-        report.iter().cloned().rev().collect()
-    };
-
-    report.iter().try_fold(None, check_neigbours).is_continue()
-        || (with_dampener
-            && (0..report.len()).any(|i| {
-                report[0..i]
-                    .iter()
-                    .chain(&report[i + 1..])
-                    .try_fold(None, check_neigbours)
-                    .is_continue()
-            }))
+type Chain = (usize, Reverse<usize>);
+
+// A bottom-up segment tree answering range maximum queries.
+struct MaxTree {
+    nodes: Vec<Option<Chain>>,
 }
 
-fn check_neigbours<'a>(prev: Option<&'a u32>, level: &'a u32) -> ControlFlow<(), Option<&'a u32>> {
-    match (prev, level) {
-        (Some(prev), level) if condition(prev, level) => Continue(Some(level)),
-        (Some(prev), level) if !condition(prev, level) => Break(()),
-        (None, level) => Continue(Some(level)),
-        _ => Break(()),
+impl MaxTree {
+    fn new(len: usize) -> Self {
+        MaxTree {
+            nodes: vec![None; 2 * len],
+        }
+    }
+
+    // Replaces the chain at `index` if the new one is better.
+    fn raise(&mut self, index: usize, chain: Chain) {
+        let mut node = index + self.nodes.len() / 2;
+        while node > 0 {
+            if self.nodes[node] >= Some(chain) {
+                break;
+            }
+            self.nodes[node] = Some(chain);
+            node /= 2;
+        }
+    }
+
+    fn max(&self, range: Range<usize>) -> Option<Chain> {
+        let offset = self.nodes.len() / 2;
+        let (mut low, mut high) = (range.start + offset, range.end + offset);
+        let mut best = None;
+        while low < high {
+            if low % 2 == 1 {
+                best = best.max(self.nodes[low]);
+                low += 1;
+            }
+            if high % 2 == 1 {
+                high -= 1;
+                best = best.max(self.nodes[high]);
+            }
+            low /= 2;
+            high /= 2;
+        }
+        best
     }
 }
 
-fn condition(prev: &u32, x: &u32) -> bool {
-    prev < x && prev + 3 >= *x
+fn safety_check(input: &Input, with_dampener: bool) -> u32 {
+    let rule = SafetyRule::default();
+    input
+        .iter()
+        .filter(|report| rule.is_safe_with(report, with_dampener as usize))
+        .count() as u32
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::Lcg;
 
     const EXAMPLE_INPUT: &str = "7 6 4 2 1\n1 2 7 8 9\n9 7 6 2 1\n1 3 2 4 5\n8 6 4 4 1\n1 3 6 7 9";
 
@@ -127,4 +231,104 @@ mod tests {
     fn undampable2() {
         assert_eq!(safety_check(&vec![vec![1, 4, 8, 10]], true), 0);
     }
+
+    #[test]
+    fn minimal_removals() {
+        let rule = SafetyRule::default();
+        let reports = input_generator(EXAMPLE_INPUT);
+        assert_eq!(
+            reports
+                .iter()
+                .map(|report| rule.removals(report))
+                .collect::<Vec<_>>(),
+            vec![vec![], vec![0, 1], vec![3, 4], vec![2], vec![3], vec![]]
+        );
+
+        assert_eq!(rule.removals(&[5, 1, 2, 9, 3, 4, 20, 5]), vec![0, 3, 6]);
+        assert!(rule.is_safe_with(&[5, 1, 2, 9, 3, 4, 20, 5], 3));
+        assert!(!rule.is_safe_with(&[5, 1, 2, 9, 3, 4, 20, 5], 2));
+        assert_eq!(rule.min_removals(&[]), 0);
+        assert_eq!(rule.min_removals(&[7]), 0);
+    }
+
+    #[test]
+    fn custom_step_bounds() {
+        let rule = SafetyRule {
+            min_step: 0,
+            max_step: 10,
+        };
+        assert_eq!(rule.min_removals(&[8, 6, 4, 4, 1]), 0);
+        assert_eq!(rule.min_removals(&[1, 2, 7, 8, 9]), 0);
+
+        let rule = SafetyRule {
+            min_step: 2,
+            max_step: 2,
+        };
+        assert_eq!(rule.removals(&[1, 3, 4, 5, 7, 8]), vec![2, 5]);
+    }
+
+    #[test]
+    fn wide_step_bounds() {
+        let rule = SafetyRule {
+            min_step: 1,
+            max_step: u32::MAX,
+        };
+        assert_eq!(rule.removals(&[0, u32::MAX, 5, 5, 7]), vec![1, 3]);
+        assert_eq!(rule.min_removals(&[u32::MAX, 0, u32::MAX]), 1);
+
+        let report = (0..100_000u32)
+            .map(|i| i.wrapping_mul(2_654_435_761))
+            .collect::<Vec<_>>();
+        let removals = rule.min_removals(&report);
+        assert!(removals > 0 && removals < report.len());
+
+        let rule = SafetyRule {
+            min_step: 0,
+            max_step: u32::MAX,
+        };
+        assert_eq!(rule.min_removals(&[3, 3, u32::MAX, 0, 3]), 2);
+    }
+
+    #[test]
+    fn mostly_removed_long_report() {
+        let rule = SafetyRule::default();
+        let report = (0..100_000)
+            .flat_map(|i| [i, 1_000_000])
+            .collect::<Vec<_>>();
+        assert_eq!(rule.min_removals(&report), 100_000);
+        assert!(!rule.is_safe_with(&report, 99_999));
+    }
+
+    #[test]
+    fn removals_match_brute_force() {
+        let rule = SafetyRule::default();
+        let mut rng = Lcg::new(7);
+        for _ in 0..300 {
+            let report = (0..8).map(|_| rng.below(12) as u32).collect::<Vec<_>>();
+
+            let brute_force = (0u32..1 << report.len())
+                .filter(|mask| {
+                    let kept = (0..report.len())
+                        .filter(|i| mask & (1 << i) == 0)
+                        .map(|i| report[i])
+                        .collect::<Vec<_>>();
+                    [true, false].into_iter().any(|ascending| {
+                        kept.windows(2).all(|w| rule.allows(w[0], w[1], ascending))
+                    })
+                })
+                .map(u32::count_ones)
+                .min()
+                .unwrap();
+
+            let removals = rule.removals(&report);
+            assert_eq!(removals.len(), brute_force as usize, "{report:?}");
+            let kept = (0..report.len())
+                .filter(|i| !removals.contains(i))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                rule.min_removals(&kept.iter().map(|&i| report[i]).collect::<Vec<_>>()),
+                0
+            );
+        }
+    }
 }
@@ -1,85 +1,139 @@
-use std::collections::HashMap;
-
-use itertools::Itertools;
+use std::collections::VecDeque;
 
 use crate::utils::{Direction, Pos};
 
-fn get_path(race_track: &Vec<Vec<char>>) -> Vec<Pos> {
-    let start_idx = race_track.iter().flatten().position(|&c| c == 'S').unwrap();
-    let end_idx = race_track.iter().flatten().position(|&c| c == 'E').unwrap();
-    let w = race_track[0].len();
-
-    let start_pos = Pos {
-        x: (start_idx % w) as i64,
-        y: (start_idx / w) as i64,
-    };
-    let end_pos = Pos {
-        x: (end_idx % w) as i64,
-        y: (end_idx / w) as i64,
-    };
-
-    let mut cur = start_pos;
-
-    let mut path = vec![start_pos];
-
-    while cur != end_pos {
-        for next in Direction::into_iter().map(|dir| cur + dir) {
-            if race_track[next] != '#' && (path.len() < 2 || path[path.len() - 2] != next) {
-                cur = next;
-                path.push(cur);
-                break;
+pub struct RaceTrack {
+    walls: Vec<bool>,
+    width: usize,
+    height: usize,
+    start: Pos,
+    end: Pos,
+}
+
+impl RaceTrack {
+    pub fn new(grid: &[Vec<char>], start: Pos, end: Pos) -> Self {
+        RaceTrack {
+            walls: grid.iter().flatten().map(|&c| c == '#').collect(),
+            width: grid[0].len(),
+            height: grid.len(),
+            start,
+            end,
+        }
+    }
+
+    fn find(grid: &[Vec<char>], field: char) -> Pos {
+        let idx = grid.iter().flatten().position(|&c| c == field).unwrap();
+        Pos {
+            x: (idx % grid[0].len()) as i64,
+            y: (idx / grid[0].len()) as i64,
+        }
+    }
+
+    fn index(&self, pos: Pos) -> Option<usize> {
+        if pos.x < 0 || pos.y < 0 || pos.x >= self.width as i64 || pos.y >= self.height as i64 {
+            return None;
+        }
+        Some(pos.y as usize * self.width + pos.x as usize)
+    }
+
+    fn distances_from(&self, source: Pos) -> Vec<usize> {
+        let mut dist = vec![usize::MAX; self.walls.len()];
+        let mut queue = VecDeque::new();
+
+        let source_idx = self.index(source).unwrap();
+        dist[source_idx] = 0;
+        queue.push_back(source);
+
+        while let Some(pos) = queue.pop_front() {
+            let cost = dist[self.index(pos).unwrap()] + 1;
+            for next in Direction::into_iter().map(|dir| pos + dir) {
+                if let Some(idx) = self.index(next) {
+                    if !self.walls[idx] && dist[idx] == usize::MAX {
+                        dist[idx] = cost;
+                        queue.push_back(next);
+                    }
+                }
             }
         }
+
+        dist
     }
+}
 
-    path
+impl From<&str> for RaceTrack {
+    fn from(input: &str) -> Self {
+        let grid = input
+            .lines()
+            .map(|line| line.chars().collect())
+            .collect::<Vec<Vec<char>>>();
+        let start = RaceTrack::find(&grid, 'S');
+        let end = RaceTrack::find(&grid, 'E');
+        RaceTrack::new(&grid, start, end)
+    }
 }
 
-fn get_neighbours_in_manhattan_range(pos: Pos, range: i64) -> impl Iterator<Item = Pos> {
+fn get_cheat_offsets(range: i64) -> Vec<(i64, i64, usize)> {
     (-range..=range)
-        .cartesian_product(-range..=range)
-        .filter(move |(i, j)| i.abs() + j.abs() <= range)
-        .map(move |(i, j)| pos + Pos::from((i, j)))
+        .flat_map(|dy| (-range..=range).map(move |dx| (dx, dy)))
+        .filter(|(dx, dy)| dx.abs() + dy.abs() <= range)
+        .map(|(dx, dy)| (dx, dy, (dx.abs() + dy.abs()) as usize))
+        .collect()
 }
 
-fn get_possible_cheat_gains(race_track: &Vec<Vec<char>>, min_gain: usize, range: i64) -> usize {
-    let path = get_path(&race_track);
-
-    let costs = path
-        .iter()
-        .enumerate()
-        .map(|(i, &pos)| (pos, i))
-        .collect::<HashMap<_, _>>();
-
-    path.iter()
-        .copied()
-        .enumerate()
-        .flat_map(|(i, pos)| {
-            let costs = &costs;
-            get_neighbours_in_manhattan_range(pos, range)
-                .filter_map(move |next| {
-                    costs.get(&next).and_then(|&cost| {
-                        cost.checked_sub(i + pos.manhattan_distance(next) as usize)
-                    })
-                })
-                .filter(|&cost| cost > 0)
-                .filter(|&cost| cost >= min_gain)
-        })
-        .count()
+pub fn get_possible_cheat_gains(race_track: &RaceTrack, min_gain: usize, range: i64) -> usize {
+    let from_start = race_track.distances_from(race_track.start);
+    let from_end = race_track.distances_from(race_track.end);
+
+    let best = from_start[race_track.index(race_track.end).unwrap()];
+    if best == usize::MAX {
+        return 0;
+    }
+
+    let offsets = get_cheat_offsets(range);
+    let width = race_track.width as i64;
+    let height = race_track.height as i64;
+
+    let mut count = 0;
+    for (idx, &to_cheat) in from_start.iter().enumerate() {
+        if to_cheat >= best {
+            continue;
+        }
+        let (x, y) = (
+            (idx % race_track.width) as i64,
+            (idx / race_track.width) as i64,
+        );
+
+        for &(dx, dy, cheat_len) in &offsets {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                continue;
+            }
+            let from_cheat = from_end[(ny * width + nx) as usize];
+            if from_cheat == usize::MAX {
+                continue;
+            }
+            let total = to_cheat + cheat_len + from_cheat;
+            if total < best && best - total >= min_gain {
+                count += 1;
+            }
+        }
+    }
+
+    count
 }
 
 #[aoc_generator(day20)]
-fn input_generator(input: &str) -> Vec<Vec<char>> {
-    input.lines().map(|line| line.chars().collect()).collect()
+fn input_generator(input: &str) -> RaceTrack {
+    RaceTrack::from(input)
 }
 
 #[aoc(day20, part1)]
-fn part1(race_track: &Vec<Vec<char>>) -> usize {
+fn part1(race_track: &RaceTrack) -> usize {
     get_possible_cheat_gains(race_track, 100, 2)
 }
 
 #[aoc(day20, part2)]
-fn part2(race_track: &Vec<Vec<char>>) -> usize {
+fn part2(race_track: &RaceTrack) -> usize {
     get_possible_cheat_gains(race_track, 100, 20)
 }
 
@@ -118,4 +172,20 @@ mod tests {
             285
         );
     }
+
+    #[test]
+    fn test_branching_maze() {
+        let race_track = input_generator(
+            "#########
+#S..#...#
+#.#.#.#.#
+#.#...#.#
+#.#####.#
+#...#..E#
+#########",
+        );
+        assert_eq!(get_possible_cheat_gains(&race_track, 1, 2), 6);
+        assert_eq!(get_possible_cheat_gains(&race_track, 4, 2), 4);
+        assert_eq!(get_possible_cheat_gains(&race_track, 1, 20), 97);
+    }
 }
@@ -1,192 +1,314 @@
-use std::{
-    collections::HashMap,
-    iter::once,
-};
+use std::{collections::HashMap, slice, str::Chars};
 
-use itertools::{repeat_n, Itertools};
+use itertools::Itertools;
 
-use crate::utils::Pos;
+use crate::utils::{Direction, Pos};
 
-struct Keypad {
-    keys: Vec<Vec<Option<char>>>,
-    pos: Pos,
+#[derive(Clone)]
+struct Move {
+    cost: u64,
+    presses: Vec<char>,
 }
 
-type FrequencyTable = HashMap<(char, char), u64>;
+type MoveTable = HashMap<(char, char), Move>;
+
+pub struct Keypad {
+    keys: HashMap<char, Pos>,
+}
 
 impl Keypad {
-    fn numeric() -> Self {
-        Self {
-            keys: vec![
-                vec![None; 5],
-                vec![None, Some('7'), Some('8'), Some('9'), None],
-                vec![None, Some('4'), Some('5'), Some('6'), None],
-                vec![None, Some('1'), Some('2'), Some('3'), None],
-                vec![None, None, Some('0'), Some('A'), None],
-                vec![None; 5],
-            ],
-            pos: Pos { x: 3, y: 4 },
-        }
+    pub const NUMERIC: &'static str = "789\n456\n123\n#0A";
+    pub const DIRECTIONAL: &'static str = "#^A\n<v>";
+
+    pub fn numeric() -> Self {
+        Keypad::from(Self::NUMERIC)
     }
 
-    fn directional() -> Self {
-        Self {
-            keys: vec![
-                vec![None; 5],
-                vec![None, None, Some('^'), Some('A'), None],
-                vec![None, Some('<'), Some('v'), Some('>'), None],
-                vec![None; 5],
-            ],
-            pos: Pos { x: 3, y: 1 },
-        }
+    pub fn directional() -> Self {
+        Keypad::from(Self::DIRECTIONAL)
     }
 
-    fn get_coordinates(&self, c: char) -> Pos {
-        let pos = self
-            .keys
+    fn key_at(&self, pos: Pos) -> Option<char> {
+        self.keys
             .iter()
-            .flatten()
-            .position(|&key| key == Some(c))
-            .unwrap();
-        Pos {
-            x: pos as i64 % self.keys[0].len() as i64,
-            y: pos as i64 / self.keys[0].len() as i64,
-        }
+            .find(|&(_, &key)| key == pos)
+            .map(|(&c, _)| c)
     }
 
-    fn sequences_to(&mut self, target: char) -> Vec<char> {
-        let start_pos = self.pos;
-        let target_pos = self.get_coordinates(target);
-        self.pos = target_pos;
+    fn best_move(
+        &self,
+        start: Pos,
+        target: Pos,
+        costs: &impl Fn(char, char) -> u64,
+    ) -> Option<Move> {
+        let (dx, dy) = (target.x - start.x, target.y - start.y);
+        let (h_key, v_key) = (
+            if dx > 0 { '>' } else { '<' },
+            if dy > 0 { 'v' } else { '^' },
+        );
+        let (h, v) = (dx.unsigned_abs() as usize, dy.unsigned_abs() as usize);
+        let extend = |prev: &Option<Move>, from: char, to: char| {
+            prev.as_ref().map(|m| Move {
+                cost: m.cost + costs(from, to),
+                presses: m.presses.iter().copied().chain([to]).collect(),
+            })
+        };
+        fn cheapest(candidates: impl IntoIterator<Item = Option<Move>>) -> Option<Move> {
+            candidates.into_iter().flatten().min_by_key(|m| m.cost)
+        }
+
+        // best[i][j] holds the cheapest way to have made i horizontal and j vertical moves,
+        // split by whether the last pressed key was the horizontal or the vertical one.
+        let mut best = vec![vec![[None::<Move>, None::<Move>]; v + 1]; h + 1];
+        let nothing_pressed = Some(Move {
+            cost: 0,
+            presses: vec![],
+        });
+
+        for i in 0..=h {
+            for j in 0..=v {
+                let pos = Pos {
+                    x: start.x + dx.signum() * i as i64,
+                    y: start.y + dy.signum() * j as i64,
+                };
+                if self.key_at(pos).is_none() {
+                    continue;
+                }
+                if i > 0 {
+                    best[i][j][0] = cheapest([
+                        (i == 1 && j == 0)
+                            .then(|| extend(&nothing_pressed, 'A', h_key))
+                            .flatten(),
+                        extend(&best[i - 1][j][0], h_key, h_key),
+                        extend(&best[i - 1][j][1], v_key, h_key),
+                    ]);
+                }
+                if j > 0 {
+                    best[i][j][1] = cheapest([
+                        (i == 0 && j == 1)
+                            .then(|| extend(&nothing_pressed, 'A', v_key))
+                            .flatten(),
+                        extend(&best[i][j - 1][0], h_key, v_key),
+                        extend(&best[i][j - 1][1], v_key, v_key),
+                    ]);
+                }
+            }
+        }
 
-        let dx = target_pos.x - start_pos.x;
-        let dy = target_pos.y - start_pos.y;
+        if h == 0 && v == 0 {
+            return extend(&nothing_pressed, 'A', 'A');
+        }
 
-        let h = repeat_n(if dx > 0 { '>' } else { '<' }, dx.abs() as usize);
-        let v = repeat_n(if dy > 0 { 'v' } else { '^' }, dy.abs() as usize);
-        let a = once('A');
-        (if dx > 0 && !self.keys[target_pos.y as usize][start_pos.x as usize].is_none() {
-            v.chain(h)
-        } else if !self.keys[start_pos.y as usize][target_pos.x as usize].is_none() {
-            h.chain(v)
-        } else {
-            v.chain(h)
-        })
-        .chain(a)
-        .collect()
+        let [last_h, last_v] = &best[h][v];
+        cheapest([extend(last_h, h_key, 'A'), extend(last_v, v_key, 'A')])
+    }
+
+    fn move_table(&self, costs: impl Fn(char, char) -> u64) -> MoveTable {
+        let mut table = HashMap::new();
+        for (&start, &start_pos) in &self.keys {
+            for (&target, &target_pos) in &self.keys {
+                if let Some(m) = self.best_move(start_pos, target_pos, &costs) {
+                    table.insert((start, target), m);
+                }
+            }
+        }
+        table
     }
 }
 
-fn get_shortest_sequence(code: &Vec<char>, n_dir_keypads: usize) -> Vec<char> {
-    let mut keypad_numeric = Keypad::numeric();
+impl From<&str> for Keypad {
+    fn from(layout: &str) -> Self {
+        let keys = layout
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars().enumerate().map(move |(x, c)| {
+                    (
+                        c,
+                        Pos {
+                            x: x as i64,
+                            y: y as i64,
+                        },
+                    )
+                })
+            })
+            .filter(|&(c, _)| c != '#' && c != ' ')
+            .collect::<HashMap<_, _>>();
 
-    let mut first_sequence_set = code
-        .iter()
-        .flat_map(|&c| keypad_numeric.sequences_to(c))
-        .collect_vec();
+        if !keys.contains_key(&'A') {
+            panic!("Keypad has no activation key");
+        }
 
-    for _ in 0..n_dir_keypads {
-        let mut keypad_directional = Keypad::directional();
-        first_sequence_set = first_sequence_set
-            .iter()
-            .flat_map(|&c| keypad_directional.sequences_to(c))
-            .collect_vec();
+        Keypad { keys }
     }
+}
 
-    first_sequence_set
+pub struct KeypadChain {
+    door: Keypad,
+    robot: Keypad,
+    // tables[level] maps a pair of keys on keypad `level + 1` to the presses on keypad `level`,
+    // where keypad 0 is the one the human presses and the last keypad is the door.
+    tables: Vec<MoveTable>,
 }
 
-fn update_frequency_table(table: &mut FrequencyTable) -> FrequencyTable {
-    let mut new_table = HashMap::new();
+impl KeypadChain {
+    pub fn new(door: Keypad, robot: Keypad, depth: usize) -> Self {
+        if "^v<>".chars().any(|c| !robot.keys.contains_key(&c)) {
+            panic!("Robot keypad is missing a direction key");
+        }
+
+        let mut tables: Vec<MoveTable> = Vec::with_capacity(depth + 1);
+        for _ in 0..depth {
+            let table = match tables.last() {
+                Some(prev) => robot.move_table(|a, b| prev[&(a, b)].cost),
+                None => robot.move_table(|_, _| 1),
+            };
+            tables.push(table);
+        }
+        let door_table = match tables.last() {
+            Some(prev) => door.move_table(|a, b| prev[&(a, b)].cost),
+            None => door.move_table(|_, _| 1),
+        };
+        tables.push(door_table);
+
+        KeypadChain {
+            door,
+            robot,
+            tables,
+        }
+    }
+
+    pub fn sequence_length(&self, code: &str) -> u64 {
+        let door_table = self.tables.last().unwrap();
+        code.chars()
+            .scan('A', |prev, c| {
+                let cost = door_table[&(*prev, c)].cost;
+                *prev = c;
+                Some(cost)
+            })
+            .sum()
+    }
+
+    pub fn shortest_sequence<'a>(&'a self, code: &'a str) -> ShortestSequence<'a> {
+        ShortestSequence {
+            chain: self,
+            code: code.chars(),
+            arms: vec!['A'; self.tables.len()],
+            stack: vec![],
+        }
+    }
+
+    pub fn run_lengths<'a>(&'a self, code: &'a str) -> impl Iterator<Item = (usize, char)> + 'a {
+        self.shortest_sequence(code).dedup_with_count()
+    }
+
+    pub fn simulate(&self, presses: impl IntoIterator<Item = char>) -> Option<String> {
+        let depth = self.tables.len() - 1;
+        let mut arms = vec![self.robot.keys[&'A']; depth];
+        arms.push(self.door.keys[&'A']);
 
-    for &(start, target) in table.keys() {
-        let moves = get_optimal_directional_path(start, target);
-        let mut prev = 'A';
-        for &m in &moves {
-            *new_table.entry((prev, m)).or_insert(0) += table[&(start, target)];
-            prev = m;
+        let mut typed = String::new();
+        for press in presses {
+            let mut level = 0;
+            let mut key = press;
+            loop {
+                let keypad = if level == depth {
+                    &self.door
+                } else {
+                    &self.robot
+                };
+                match key {
+                    'A' => {}
+                    '^' | 'v' | '<' | '>' => {
+                        arms[level] += Direction::from(key);
+                        keypad.key_at(arms[level])?;
+                        break;
+                    }
+                    _ => return None,
+                }
+                key = keypad.key_at(arms[level])?;
+                if level == depth {
+                    typed.push(key);
+                    break;
+                }
+                level += 1;
+            }
         }
+
+        Some(typed)
     }
 
-    new_table
+    pub fn complexity(&self, code: &str) -> u64 {
+        let numeric = code
+            .chars()
+            .filter(char::is_ascii_digit)
+            .collect::<String>()
+            .parse::<u64>()
+            .unwrap_or(0);
+        numeric * self.sequence_length(code)
+    }
 }
 
-fn get_optimal_directional_path(start: char, target: char) -> Vec<char> {
-    (match (start, target) {
-        (start, target) if start == target => "",
-        ('A', '^') | ('>', 'v') | ('v', '<') => "<",
-        ('A', '>') | ('^', 'v') => "v",
-        ('A', 'v') => "<v",
-        ('A', '<') => "v<<",
-        ('^', 'A') | ('<', 'v') | ('v', '>') => ">",
-        ('>', 'A') => "^",
-        ('<', 'A') => ">>^",
-        ('v', 'A') => "^>",
-        ('^', '>') => "v>",
-        ('<', '^') => ">^",
-        ('>', '^') => "<^",
-        ('<', '>') => ">>",
-        ('>', '<') => "<<",
-        ('^', '<') => "v<",
-        _ => unreachable!(),
-    })
-    .chars()
-    .chain(once('A'))
-    .collect()
+pub struct ShortestSequence<'a> {
+    chain: &'a KeypadChain,
+    code: Chars<'a>,
+    arms: Vec<char>,
+    stack: Vec<(usize, slice::Iter<'a, char>)>,
 }
 
-fn use_freq_tables(input: &str, n: usize) -> u64 {
-    let mut keypad_numeric = Keypad::numeric();
-    let sequences = input
-        .lines()
-        .flat_map(|line| line.chars())
-        .flat_map(|c| keypad_numeric.sequences_to(c))
-        .collect_vec();
+impl Iterator for ShortestSequence<'_> {
+    type Item = char;
 
-    let mut freq_table = HashMap::new();
+    fn next(&mut self) -> Option<char> {
+        loop {
+            let (level, key) = match self.stack.last_mut() {
+                Some((level, presses)) => match presses.next() {
+                    Some(&key) => (*level, key),
+                    None => {
+                        self.stack.pop();
+                        continue;
+                    }
+                },
+                None => (self.chain.tables.len(), self.code.next()?),
+            };
 
-    freq_table.insert(('A', sequences[0]), 1);
-    for pair in sequences.windows(2) {
-        *freq_table.entry((pair[0], pair[1])).or_insert(0) += 1;
-    }
+            if level == 0 {
+                return Some(key);
+            }
 
-    let freq_table = (0..n).fold(freq_table, |mut table, _| {
-        update_frequency_table(&mut table)
-    });
-    freq_table.values().sum()
+            let prev = self.arms[level - 1];
+            self.arms[level - 1] = key;
+            let presses = &self.chain.tables[level - 1][&(prev, key)].presses;
+            self.stack.push((level - 1, presses.iter()));
+        }
+    }
 }
 
-#[aoc(day21, part1, loop)]
-fn part1(input: &str) -> u64 {
-    input
-        .lines()
-        .map(|line| {
-            line[0..3].parse::<u64>().unwrap()
-                * get_shortest_sequence(&line.chars().collect(), 2).len() as u64
-        })
-        .sum()
+fn total_complexity(input: &str, depth: usize) -> u64 {
+    let chain = KeypadChain::new(Keypad::numeric(), Keypad::directional(), depth);
+    input.lines().map(|line| chain.complexity(line)).sum()
 }
 
-#[aoc(day21, part1, freq_tables)]
-fn part12(input: &str) -> u64 {
-    input
-        .lines()
-        .map(|line| line[0..3].parse::<u64>().unwrap() * use_freq_tables(line, 2) as u64)
-        .sum()
+#[aoc(day21, part1)]
+fn part1(input: &str) -> u64 {
+    total_complexity(input, 2)
 }
 
 #[aoc(day21, part2)]
 fn part2(input: &str) -> u64 {
-    input
-        .lines()
-        .map(|line| line[0..3].parse::<u64>().unwrap() * use_freq_tables(line, 25) as u64)
-        .sum()
+    total_complexity(input, 25)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLE_INPUT: &str = "029A
+980A
+179A
+456A
+379A";
+
     #[test]
     fn test_part1() {
         assert_eq!(part1("029A"), 68 * 29);
@@ -195,4 +317,49 @@ mod tests {
         assert_eq!(part1("456A"), 64 * 456);
         assert_eq!(part1("379A"), 64 * 379);
     }
+
+    #[test]
+    fn test_part2() {
+        assert_eq!(part2(EXAMPLE_INPUT), 154115708116294);
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        let direct = KeypadChain::new(Keypad::numeric(), Keypad::directional(), 0);
+        assert_eq!(direct.sequence_length("029A"), 12);
+
+        let chain = KeypadChain::new(Keypad::from("A12\n#34"), Keypad::from("<v>\n#^A"), 1);
+        assert_eq!(chain.sequence_length("4"), 8);
+        assert_eq!(chain.sequence_length("A"), 1);
+    }
+
+    #[test]
+    fn test_shortest_sequence() {
+        let chain = KeypadChain::new(Keypad::numeric(), Keypad::directional(), 2);
+        for code in EXAMPLE_INPUT.lines() {
+            let sequence = chain.shortest_sequence(code).collect::<String>();
+            assert_eq!(sequence.len() as u64, chain.sequence_length(code));
+            assert_eq!(chain.simulate(sequence.chars()).as_deref(), Some(code));
+        }
+
+        let chain = KeypadChain::new(Keypad::numeric(), Keypad::directional(), 0);
+        assert_eq!(
+            chain.shortest_sequence("029A").collect::<String>(),
+            "<A^A^^>AvvvA"
+        );
+        assert_eq!(chain.simulate("<<A".chars()), None);
+        assert_eq!(chain.simulate("<x".chars()), None);
+    }
+
+    #[test]
+    fn test_run_lengths() {
+        let chain = KeypadChain::new(Keypad::numeric(), Keypad::directional(), 8);
+        let runs = chain.run_lengths("379A").collect_vec();
+        assert_eq!(
+            runs.iter().map(|&(n, _)| n as u64).sum::<u64>(),
+            chain.sequence_length("379A")
+        );
+        let presses = runs.iter().flat_map(|&(n, c)| std::iter::repeat_n(c, n));
+        assert_eq!(chain.simulate(presses).as_deref(), Some("379A"));
+    }
 }
@@ -1,5 +1,3 @@
-use std::collections::HashMap;
-
 use itertools::Itertools;
 
 const MODULUS: u64 = 16777215;
@@ -8,14 +6,120 @@ const N: usize = 2000;
 fn evolve_secret(secret: u64) -> u64 {
     let secret = (secret ^ (secret << 6)) & MODULUS;
     let secret = (secret ^ (secret >> 5)) & MODULUS;
-    let secret = (secret ^ (secret << 11)) & MODULUS;
-    secret
+    (secret ^ (secret << 11)) & MODULUS
+}
+
+fn undo_shift_left(secret: u64, shift: u32) -> u64 {
+    (0..24 / shift).fold(secret, |prev, _| secret ^ ((prev << shift) & MODULUS))
 }
 
-struct PseudoRandom {
+fn undo_shift_right(secret: u64, shift: u32) -> u64 {
+    (0..24 / shift).fold(secret, |prev, _| secret ^ (prev >> shift))
+}
+
+pub fn inverse_evolve(secret: u64) -> u64 {
+    let secret = undo_shift_left(secret, 11);
+    let secret = undo_shift_right(secret, 5);
+    undo_shift_left(secret, 6)
+}
+
+const BITS: usize = 24;
+
+// A linear map over GF(2)^24, stored as the images of the unit vectors.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct BitMatrix {
+    columns: [u64; BITS],
+}
+
+impl BitMatrix {
+    fn identity() -> Self {
+        BitMatrix {
+            columns: std::array::from_fn(|i| 1 << i),
+        }
+    }
+
+    fn from_linear_map(f: impl Fn(u64) -> u64) -> Self {
+        BitMatrix {
+            columns: std::array::from_fn(|i| f(1 << i)),
+        }
+    }
+
+    fn apply(&self, vector: u64) -> u64 {
+        (0..BITS)
+            .filter(|&i| vector >> i & 1 == 1)
+            .fold(0, |acc, i| acc ^ self.columns[i])
+    }
+
+    fn compose(&self, other: &BitMatrix) -> Self {
+        BitMatrix {
+            columns: other.columns.map(|column| self.apply(column)),
+        }
+    }
+
+    fn pow(&self, mut exp: u64) -> Self {
+        let mut result = BitMatrix::identity();
+        let mut base = *self;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.compose(&base);
+            }
+            base = base.compose(&base);
+            exp >>= 1;
+        }
+        result
+    }
+}
+
+// 2^24 - 1 = 3^2 * 5 * 7 * 13 * 17 * 241
+const GROUP_ORDER_FACTORS: [u64; 6] = [3, 5, 7, 13, 17, 241];
+
+pub struct PseudoRandom {
     secret: u64,
 }
 
+impl PseudoRandom {
+    pub fn new(secret: u64) -> Self {
+        PseudoRandom { secret }
+    }
+
+    pub fn secret(&self) -> u64 {
+        self.secret
+    }
+
+    pub fn skip(self, n: u64) -> Self {
+        let evolve = BitMatrix::from_linear_map(evolve_secret);
+        PseudoRandom::new(evolve.pow(n).apply(self.secret))
+    }
+
+    pub fn rewind(self, n: u64) -> Self {
+        let inverse = BitMatrix::from_linear_map(inverse_evolve);
+        PseudoRandom::new(inverse.pow(n).apply(self.secret))
+    }
+
+    pub fn cycle_length(&self) -> u64 {
+        let evolve = BitMatrix::from_linear_map(evolve_secret);
+        let returns_after = |n: u64| evolve.pow(n).apply(self.secret) == self.secret;
+
+        if returns_after(MODULUS) {
+            let mut length = MODULUS;
+            for factor in GROUP_ORDER_FACTORS {
+                while length.is_multiple_of(factor) && returns_after(length / factor) {
+                    length /= factor;
+                }
+            }
+            return length;
+        }
+
+        let mut secret = evolve_secret(self.secret);
+        let mut length = 1;
+        while secret != self.secret {
+            secret = evolve_secret(secret);
+            length += 1;
+        }
+        length
+    }
+}
+
 impl Iterator for PseudoRandom {
     type Item = (u64, u8, i8);
 
@@ -40,38 +144,76 @@ fn part1(input: &str) -> u64 {
         .sum()
 }
 
-#[aoc(day22, part2)]
-fn part2(input: &str) -> u64 {
-    let price_lists = input
-        .lines()
-        .map(|line| {
-            let secret = line.parse().unwrap();
-            let prng = PseudoRandom { secret };
-            let mut sequence_price = HashMap::new();
-
-            for window in prng.take(N).collect_vec().windows(4) {
-                let delta_sequence = window.iter().map(|(_, _, delta)| *delta).collect_vec();
-                let price = window[3].1;
-                sequence_price.entry(delta_sequence).or_insert(price);
+const DELTAS: usize = 19;
+const SEQUENCES: usize = DELTAS * DELTAS * DELTAS * DELTAS;
+
+fn encode_delta(delta: i8) -> usize {
+    (delta + 9) as usize
+}
+
+fn decode_sequence(mut index: usize) -> [i8; 4] {
+    let mut sequence = [0; 4];
+    for delta in sequence.iter_mut().rev() {
+        *delta = (index % DELTAS) as i8 - 9;
+        index /= DELTAS;
+    }
+    sequence
+}
+
+fn accumulate_bananas(secrets: &[u64], totals: &mut [u32]) {
+    let mut last_seen = vec![usize::MAX; SEQUENCES];
+
+    for (buyer, &secret) in secrets.iter().enumerate() {
+        let mut index = 0;
+        for (i, (_, price, delta)) in (PseudoRandom { secret }).take(N).enumerate() {
+            index = (index * DELTAS + encode_delta(delta)) % SEQUENCES;
+            if i >= 3 && last_seen[index] != buyer {
+                last_seen[index] = buyer;
+                totals[index] += price as u32;
             }
+        }
+    }
+}
 
-            sequence_price
-        })
-        .collect_vec();
+pub fn best_sequence(secrets: &[u64]) -> ([i8; 4], u64) {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = secrets.len().div_ceil(threads).max(1);
+
+    let totals = std::thread::scope(|scope| {
+        secrets
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut totals = vec![0; SEQUENCES];
+                    accumulate_bananas(chunk, &mut totals);
+                    totals
+                })
+            })
+            .collect_vec()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .fold(vec![0; SEQUENCES], |mut acc, totals| {
+                acc.iter_mut().zip(totals).for_each(|(a, t)| *a += t);
+                acc
+            })
+    });
 
-    price_lists
+    let (index, &bananas) = totals
         .iter()
-        .flat_map(|price_list| price_list.keys())
-        .sorted()
-        .dedup()
-        .map(|sequence| {
-            price_lists
-                .iter()
-                .map(|price_list| *price_list.get(sequence).unwrap_or(&0) as u64)
-                .sum()
-        })
-        .max()
-        .unwrap()
+        .enumerate()
+        .max_by_key(|&(index, &bananas)| (bananas, std::cmp::Reverse(index)))
+        .unwrap();
+
+    (decode_sequence(index), bananas as u64)
+}
+
+#[aoc(day22, part2)]
+fn part2(input: &str) -> u64 {
+    let secrets = input
+        .lines()
+        .map(|line| line.parse().unwrap())
+        .collect_vec();
+    best_sequence(&secrets).1
 }
 
 #[cfg(test)]
@@ -92,6 +234,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_skip_and_rewind() {
+        let expected = PseudoRandom::new(123).nth(N - 1).unwrap().0;
+        assert_eq!(PseudoRandom::new(123).skip(N as u64).secret(), expected);
+        assert_eq!(PseudoRandom::new(expected).rewind(N as u64).secret(), 123);
+
+        let far = PseudoRandom::new(123).skip(1_000_000_000_000);
+        assert_eq!(far.rewind(1_000_000_000_000).secret(), 123);
+    }
+
+    #[test]
+    fn test_inverse_evolve() {
+        for secret in [0, 1, 123, 15887950, MODULUS] {
+            assert_eq!(inverse_evolve(evolve_secret(secret)), secret);
+        }
+        assert_eq!(inverse_evolve(15887950), 123);
+    }
+
+    #[test]
+    fn test_cycle_length() {
+        assert_eq!(PseudoRandom::new(0).cycle_length(), 1);
+        assert_eq!(PseudoRandom::new(123).cycle_length(), MODULUS);
+    }
+
     #[test]
     fn part1_example() {
         assert_eq!(
@@ -117,4 +283,9 @@ mod tests {
             23
         );
     }
+
+    #[test]
+    fn best_sequence_example() {
+        assert_eq!(best_sequence(&[1, 2, 3, 2024]), ([-2, 1, -1, 3], 23));
+    }
 }
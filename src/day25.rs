@@ -1,74 +1,187 @@
-use std::iter::zip;
+use std::collections::HashMap;
 
-use itertools::repeat_n;
-
-type Key = Vec<usize>;
-type Lock = Vec<usize>;
+use itertools::Itertools;
 
 const HEIGHT: usize = 5;
 
-#[aoc_generator(day25)]
-fn input_generator(input: &str) -> (Vec<Key>, Vec<Lock>) {
-    let mut keys = Vec::new();
-    let mut locks = Vec::new();
-
-    let lines = input.lines();
-    let mut tmp = vec![];
-    let mut is_key = false;
-
-    for line in lines {
-        if tmp.is_empty() {
-            is_key = line.chars().all(|c| c == '#');
-            tmp.extend(repeat_n(if is_key { 1 } else { 0 }, line.len()));
-        } else if line.is_empty() {
-            for i in 0..tmp.len() {
-                tmp[i] -= 1;
+// Schematics and lines are numbered from 0 in input order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchematicError {
+    InconsistentWidth { schematic: usize, line: usize },
+    WrongHeight { schematic: usize, height: usize },
+    TooWide { schematic: usize, width: usize },
+    UnknownKind { schematic: usize },
+    InvalidColumn { schematic: usize, column: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Key,
+    Lock,
+}
+
+// Every column takes HEIGHT bits. Keys fill their bits from the top of the column and locks
+// from the bottom, so a key fits a lock exactly when their masks don't overlap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schematic {
+    kind: Kind,
+    heights: Vec<usize>,
+    mask: u64,
+}
+
+impl Schematic {
+    pub fn heights(&self) -> &[usize] {
+        &self.heights
+    }
+
+    pub fn mask(&self) -> u64 {
+        self.mask
+    }
+
+    fn parse(schematic: usize, lines: &[&str]) -> Result<Self, SchematicError> {
+        if lines.len() != HEIGHT + 2 {
+            return Err(SchematicError::WrongHeight {
+                schematic,
+                height: lines.len(),
+            });
+        }
+        let width = lines[0].len();
+        if let Some(line) = lines.iter().position(|line| line.len() != width) {
+            return Err(SchematicError::InconsistentWidth { schematic, line });
+        }
+        if width * HEIGHT > u64::BITS as usize {
+            return Err(SchematicError::TooWide { schematic, width });
+        }
+
+        let filled = |line: &str| line.bytes().all(|b| b == b'#');
+        let empty = |line: &str| line.bytes().all(|b| b == b'.');
+        let kind = match (lines[0], lines[HEIGHT + 1]) {
+            (top, bottom) if filled(top) && empty(bottom) => Kind::Key,
+            (top, bottom) if empty(top) && filled(bottom) => Kind::Lock,
+            _ => return Err(SchematicError::UnknownKind { schematic }),
+        };
+
+        let mut heights = Vec::with_capacity(width);
+        let mut mask = 0;
+        for column in 0..width {
+            let mut cells = lines[1..=HEIGHT]
+                .iter()
+                .map(|line| line.as_bytes()[column])
+                .collect_vec();
+            if kind == Kind::Lock {
+                cells.reverse();
             }
-            if is_key {
-                keys.push(tmp.clone());
-            } else {
-                locks.push(tmp.clone());
+
+            let height = cells.iter().take_while(|&&b| b == b'#').count();
+            if cells[height..].iter().any(|&b| b != b'.') {
+                return Err(SchematicError::InvalidColumn { schematic, column });
             }
-            tmp.clear();
-        } else {
-            for (i, c) in line.chars().enumerate() {
-                tmp[i] += if c == '#' { 1 } else { 0 };
+
+            let bits = (1 << height) - 1;
+            let bits = match kind {
+                Kind::Key => bits,
+                Kind::Lock => bits << (HEIGHT - height),
+            };
+            heights.push(height);
+            mask |= bits << (column * HEIGHT);
+        }
+
+        Ok(Schematic {
+            kind,
+            heights,
+            mask,
+        })
+    }
+}
+
+pub struct Schematics {
+    keys: Vec<Schematic>,
+    locks: Vec<Schematic>,
+}
+
+impl TryFrom<&str> for Schematics {
+    type Error = SchematicError;
+
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        let mut keys = Vec::new();
+        let mut locks = Vec::new();
+
+        let lines = input.lines().collect_vec();
+        for (i, block) in lines
+            .split(|line| line.is_empty())
+            .filter(|block| !block.is_empty())
+            .enumerate()
+        {
+            let schematic = Schematic::parse(i, block)?;
+            match schematic.kind {
+                Kind::Key => keys.push(schematic),
+                Kind::Lock => locks.push(schematic),
             }
         }
+
+        Ok(Schematics { keys, locks })
+    }
+}
+
+impl Schematics {
+    pub fn keys(&self) -> &[Schematic] {
+        &self.keys
+    }
+
+    pub fn locks(&self) -> &[Schematic] {
+        &self.locks
+    }
+
+    // Indices of all schematics sharing each height profile.
+    fn buckets(schematics: &[Schematic]) -> Vec<(u64, Vec<usize>)> {
+        let mut buckets = HashMap::<u64, Vec<usize>>::new();
+        for (i, schematic) in schematics.iter().enumerate() {
+            buckets.entry(schematic.mask).or_default().push(i);
+        }
+        buckets.into_iter().sorted().collect()
+    }
+
+    fn fitting_buckets(&self) -> impl Iterator<Item = (Vec<usize>, Vec<usize>)> {
+        let locks = Self::buckets(&self.locks);
+        Self::buckets(&self.keys)
+            .into_iter()
+            .cartesian_product(locks)
+            .filter(|((key, _), (lock, _))| key & lock == 0)
+            .map(|((_, keys), (_, locks))| (keys, locks))
     }
 
-    for i in 0..tmp.len() {
-        tmp[i] -= 1;
+    // Only the distinct height profiles are compared, at most 6^width of each kind.
+    pub fn count_fitting(&self) -> usize {
+        self.fitting_buckets()
+            .map(|(keys, locks)| keys.len() * locks.len())
+            .sum()
     }
-    if is_key {
-        keys.push(tmp.clone());
-    } else {
-        locks.push(tmp.clone());
+
+    // Every (key, lock) pair of indices that fit together, in ascending order.
+    pub fn fitting_pairs(&self) -> Vec<(usize, usize)> {
+        self.fitting_buckets()
+            .flat_map(|(keys, locks)| keys.into_iter().cartesian_product(locks))
+            .sorted()
+            .collect()
     }
+}
 
-    (keys, locks)
+#[aoc_generator(day25)]
+fn input_generator(input: &str) -> Schematics {
+    Schematics::try_from(input).unwrap()
 }
 
 #[aoc(day25, part1)]
-fn part1((keys, locks): &(Vec<Key>, Vec<Lock>)) -> usize {
-    let mut res = 0;
-
-    keys.iter().for_each(|key| {
-        locks.iter().for_each(|lock| {
-            res += zip(key, lock).all(|(k, l)| k + l <= HEIGHT) as usize;
-        });
-    });
-
-    res
+fn part1(schematics: &Schematics) -> usize {
+    schematics.count_fitting()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::Lcg;
 
-    #[test]
-    fn sample1() {
-        let input = "#####
+    const EXAMPLE_INPUT: &str = "#####
 .####
 .####
 .####
@@ -107,7 +220,104 @@ mod tests {
 #.#..
 #.#.#
 #####";
-        let input = input_generator(input);
+
+    #[test]
+    fn sample1() {
+        let input = input_generator(EXAMPLE_INPUT);
         assert_eq!(part1(&input), 3);
     }
+
+    #[test]
+    fn test_heights_and_pairs() {
+        let schematics = input_generator(EXAMPLE_INPUT);
+        assert_eq!(schematics.keys()[0].heights(), [0, 5, 3, 4, 3]);
+        assert_eq!(schematics.locks()[0].heights(), [5, 0, 2, 1, 3]);
+        assert_eq!(schematics.fitting_pairs(), vec![(0, 2), (1, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn test_validation() {
+        let block = EXAMPLE_INPUT.split("\n\n").collect_vec();
+        let parse = |input: &str| Schematics::try_from(input).err();
+
+        assert_eq!(
+            parse(&block[0].replacen(".####", ".###", 1)),
+            Some(SchematicError::InconsistentWidth {
+                schematic: 0,
+                line: 1
+            })
+        );
+        assert_eq!(
+            parse(&format!("{}\n\n{}", block[0], &block[1][6..])),
+            Some(SchematicError::WrongHeight {
+                schematic: 1,
+                height: 6
+            })
+        );
+        assert_eq!(
+            parse(&block[2].replace("#####", "####.")),
+            Some(SchematicError::UnknownKind { schematic: 0 })
+        );
+        assert_eq!(
+            parse(&block[0].replacen(".#...", ".#..#", 1)),
+            Some(SchematicError::InvalidColumn {
+                schematic: 0,
+                column: 4
+            })
+        );
+        assert_eq!(
+            parse(&format!(
+                "{}{}",
+                "#".repeat(13),
+                "\n.............".repeat(6)
+            )),
+            Some(SchematicError::TooWide {
+                schematic: 0,
+                width: 13
+            })
+        );
+    }
+
+    #[test]
+    fn test_buckets_match_pairwise() {
+        let mut rng = Lcg::new(99);
+        let mut random = |max: u64| rng.below(max) as usize;
+
+        let mut input = Vec::new();
+        for _ in 0..200 {
+            let heights = (0..5).map(|_| random(6)).collect_vec();
+            let is_key = random(2) == 0;
+            let mut rows = (0..HEIGHT + 2).map(|row| {
+                heights
+                    .iter()
+                    .map(|&h| {
+                        let depth = if is_key { row } else { HEIGHT + 1 - row };
+                        if depth <= h {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>()
+            });
+            input.push(rows.join("\n"));
+        }
+        let schematics = input_generator(&input.join("\n\n"));
+
+        let pairwise = schematics
+            .keys()
+            .iter()
+            .enumerate()
+            .cartesian_product(schematics.locks().iter().enumerate())
+            .filter(|((_, key), (_, lock))| {
+                key.heights()
+                    .iter()
+                    .zip(lock.heights())
+                    .all(|(k, l)| k + l <= HEIGHT)
+            })
+            .map(|((k, _), (l, _))| (k, l))
+            .collect_vec();
+        assert_eq!(schematics.fitting_pairs(), pairwise);
+        assert_eq!(schematics.count_fitting(), pairwise.len());
+    }
 }
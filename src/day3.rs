@@ -1,33 +1,220 @@
-use regex::Regex;
+use std::ops::Range;
 
-#[aoc(day3, part1)]
-fn part1(input: &str) -> u64 {
-    let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)").unwrap();
-    re.captures_iter(input)
-        .map(|cap| {
-            let a = cap[1].parse::<u64>().unwrap();
-            let b = cap[2].parse::<u64>().unwrap();
-            a * b
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction<'a> {
+    Mul(u64, u64),
+    Do,
+    Dont,
+    Custom { name: &'a str, args: Vec<u64> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub instruction: Instruction<'a>,
+    pub span: Range<usize>,
+}
+
+// Finds `name(arg,...)` instructions anywhere in corrupted memory. Arguments are 1-3 digit
+// numbers, anything else is skipped.
+pub struct Scanner<'a> {
+    input: &'a [u8],
+    pos: usize,
+    custom: Vec<(&'a str, usize)>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Scanner {
+            input: input.as_bytes(),
+            pos: 0,
+            custom: Vec::new(),
+        }
+    }
+
+    pub fn with_instruction(mut self, name: &'a str, arity: usize) -> Self {
+        self.custom.push((name, arity));
+        self
+    }
+
+    fn number(&self, pos: &mut usize) -> Option<u64> {
+        let digits = self.input[*pos..]
+            .iter()
+            .take(3)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if digits == 0 {
+            return None;
+        }
+        let number = self.input[*pos..*pos + digits]
+            .iter()
+            .fold(0, |n, &b| n * 10 + (b - b'0') as u64);
+        *pos += digits;
+        Some(number)
+    }
+
+    fn expect(&self, pos: &mut usize, byte: u8) -> Option<()> {
+        (self.input.get(*pos) == Some(&byte)).then(|| *pos += 1)
+    }
+
+    // Parses `name(args)` at the current position and returns the arguments and the end offset.
+    fn call(&self, name: &str, arity: usize) -> Option<(Vec<u64>, usize)> {
+        let mut pos = self.pos;
+        if !self.input[pos..].starts_with(name.as_bytes()) {
+            return None;
+        }
+        pos += name.len();
+        self.expect(&mut pos, b'(')?;
+
+        let mut args = Vec::with_capacity(arity);
+        for i in 0..arity {
+            if i > 0 {
+                self.expect(&mut pos, b',')?;
+            }
+            args.push(self.number(&mut pos)?);
+        }
+
+        self.expect(&mut pos, b')')?;
+        Some((args, pos))
+    }
+
+    fn instruction_at(&self) -> Option<(Instruction<'a>, usize)> {
+        if let Some((args, end)) = self.call("mul", 2) {
+            return Some((Instruction::Mul(args[0], args[1]), end));
+        }
+        if let Some((_, end)) = self.call("do", 0) {
+            return Some((Instruction::Do, end));
+        }
+        if let Some((_, end)) = self.call("don't", 0) {
+            return Some((Instruction::Dont, end));
+        }
+        self.custom.iter().find_map(|&(name, arity)| {
+            self.call(name, arity)
+                .map(|(args, end)| (Instruction::Custom { name, args }, end))
+        })
+    }
+}
+
+impl<'a> Iterator for Scanner<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.input.len() {
+            if let Some((instruction, end)) = self.instruction_at() {
+                let span = self.pos..end;
+                self.pos = end;
+                return Some(Token { instruction, span });
+            }
+            self.pos += 1;
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Add(u64),
+    Skipped,
+    None,
+}
+
+pub trait Semantics {
+    fn execute(&mut self, instruction: &Instruction) -> Effect;
+}
+
+// Every `mul` counts.
+pub struct Unconditional;
+
+impl Semantics for Unconditional {
+    fn execute(&mut self, instruction: &Instruction) -> Effect {
+        match *instruction {
+            Instruction::Mul(a, b) => Effect::Add(a * b),
+            _ => Effect::None,
+        }
+    }
+}
+
+// `do()` and `don't()` switch the following `mul`s on and off.
+pub struct Conditional {
+    pub enabled: bool,
+}
+
+impl Default for Conditional {
+    fn default() -> Self {
+        Conditional { enabled: true }
+    }
+}
+
+impl Semantics for Conditional {
+    fn execute(&mut self, instruction: &Instruction) -> Effect {
+        match *instruction {
+            Instruction::Mul(a, b) if self.enabled => Effect::Add(a * b),
+            Instruction::Mul(..) => Effect::Skipped,
+            Instruction::Do => {
+                self.enabled = true;
+                Effect::None
+            }
+            Instruction::Dont => {
+                self.enabled = false;
+                Effect::None
+            }
+            Instruction::Custom { .. } => Effect::None,
+        }
+    }
+}
+
+pub fn run<'a>(
+    tokens: impl IntoIterator<Item = Token<'a>>,
+    semantics: &mut impl Semantics,
+) -> Vec<(Token<'a>, Effect)> {
+    tokens
+        .into_iter()
+        .map(|token| {
+            let effect = semantics.execute(&token.instruction);
+            (token, effect)
+        })
+        .collect()
+}
+
+pub fn total(trace: &[(Token, Effect)]) -> u64 {
+    trace
+        .iter()
+        .map(|(_, effect)| match effect {
+            Effect::Add(value) => *value,
+            _ => 0,
         })
         .sum()
 }
 
+// Debug view of the input with counted instructions wrapped in `[...]` and skipped ones in `{...}`.
+pub fn highlight(input: &str, trace: &[(Token, Effect)]) -> String {
+    let mut highlighted = String::with_capacity(input.len());
+    let mut pos = 0;
+
+    for (token, effect) in trace {
+        let (open, close) = match effect {
+            Effect::Add(_) => ('[', ']'),
+            Effect::Skipped => ('{', '}'),
+            Effect::None => continue,
+        };
+        highlighted.push_str(&input[pos..token.span.start]);
+        highlighted.push(open);
+        highlighted.push_str(&input[token.span.clone()]);
+        highlighted.push(close);
+        pos = token.span.end;
+    }
+
+    highlighted.push_str(&input[pos..]);
+    highlighted
+}
+
+#[aoc(day3, part1)]
+fn part1(input: &str) -> u64 {
+    total(&run(Scanner::new(input), &mut Unconditional))
+}
+
 #[aoc(day3, part2)]
 fn part2(input: &str) -> u64 {
-    let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)|(d)o\(\)|do(n)'t\(\)").unwrap();
-    re.captures_iter(input)
-        .fold((1, 0), |(enabled, sum), cap| {
-            if let Some(_) = cap.get(3) {
-                return (1, sum);
-            } else if let Some(_) = cap.get(4) {
-                return (0, sum);
-            } else {
-                let a = cap[1].parse::<u64>().unwrap();
-                let b = cap[2].parse::<u64>().unwrap();
-                (enabled, sum + a * b * enabled)
-            }
-        })
-        .1
+    total(&run(Scanner::new(input), &mut Conditional::default()))
 }
 
 #[cfg(test)]
@@ -49,4 +236,63 @@ mod tests {
             48
         );
     }
+
+    #[test]
+    fn test_tokens() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(1234,5)mul(1,2 )do()";
+        let tokens = Scanner::new(input).collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            vec![
+                Token {
+                    instruction: Instruction::Mul(2, 4),
+                    span: 1..9
+                },
+                Token {
+                    instruction: Instruction::Dont,
+                    span: 20..27
+                },
+                Token {
+                    instruction: Instruction::Mul(5, 5),
+                    span: 28..36
+                },
+                Token {
+                    instruction: Instruction::Do,
+                    span: 57..61
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        let input = "xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let trace = run(Scanner::new(input), &mut Conditional::default());
+        assert_eq!(
+            highlight(input, &trace),
+            "x[mul(2,4)]&mul[3,7]!^don't()_{mul(5,5)}+mul(32,64]({mul(11,8)}undo()?[mul(8,5)])"
+        );
+    }
+
+    #[test]
+    fn test_custom_instruction() {
+        struct WithAdd(Conditional);
+
+        impl Semantics for WithAdd {
+            fn execute(&mut self, instruction: &Instruction) -> Effect {
+                match instruction {
+                    Instruction::Custom { name: "add", args } if self.0.enabled => {
+                        Effect::Add(args.iter().sum())
+                    }
+                    Instruction::Custom { name: "add", .. } => Effect::Skipped,
+                    _ => self.0.execute(instruction),
+                }
+            }
+        }
+
+        let input = "add(1,2,3)mul(2,3)don't()add(100,1,1)do()add(4,5,6)add(1,2)";
+        let scanner = Scanner::new(input).with_instruction("add", 3);
+        let trace = run(scanner, &mut WithAdd(Conditional::default()));
+        assert_eq!(total(&trace), 6 + 6 + 15);
+    }
 }
@@ -1,118 +1,195 @@
 use itertools::Itertools;
-use std::cmp::min;
-type Input = Vec<String>;
 
-#[aoc_generator(day4)]
-pub fn input_generator(input: &str) -> Input {
-    input.lines().map(|line| line.chars().collect()).collect()
-}
+use crate::utils::Pos;
 
-#[aoc(day4, part1)]
-fn part1(input: &Input) -> usize {
-    let n_rows = input.len();
-    let n_cols = input[0].len();
-
-    input
-        .iter()
-        .map(find_xmas)
-        .chain((0..n_cols).map(|i| {
-            find_xmas(
-                &input
-                    .iter()
-                    .map(|row| row.chars().nth(i).unwrap())
-                    .collect(),
-            )
-        }))
-        .chain(
-            (1 - n_rows as i32..n_cols as i32)
-                .map(|offset| {
-                    if offset > 0 {
-                        [0, offset as usize]
-                    } else {
-                        [-offset as usize, 0]
-                    }
-                })
-                .map(|[row_offset, col_offset]| {
-                    (0..min(n_rows - row_offset, n_cols - col_offset))
-                        .map(|i| input[i + row_offset].chars().nth(i + col_offset).unwrap())
-                        .collect()
-                })
-                .map(|input| find_xmas(&input)),
-        )
-        .chain(
-            (0..n_rows + n_cols - 1)
-                .map(|offset| {
-                    if offset < n_rows {
-                        [offset, 0]
-                    } else {
-                        [n_rows - 1, offset - n_rows + 1]
-                    }
-                })
-                .map(|[row_offset, col_offset]| {
-                    (0..min(row_offset + 1, n_cols - col_offset))
-                        .map(|i| input[row_offset - i].chars().nth(col_offset + i).unwrap())
-                        .collect()
-                })
-                .map(|input| find_xmas(&input)),
-        )
-        .sum()
+const DIRECTIONS: [(i64, i64); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+pub struct WordSearch {
+    grid: Vec<Vec<char>>,
 }
 
-fn _get_column<'a, T>(arr_2d: &'a [&'a [T]]) -> Vec<&'a T>
-where
-    T: Copy,
-{
-    (0..arr_2d.len())
-        .map(|i| {
-            arr_2d
-                .iter()
-                .flat_map(|row| row.iter().skip(i))
-                .next()
-                .unwrap()
-        })
-        .collect()
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub cells: Vec<Pos>,
 }
 
+// A small template where '.' matches any letter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    cells: Vec<(Pos, char)>,
+}
 
-fn find_xmas(str: &String) -> usize {
-    str.match_indices("XMAS").count() + str.match_indices("SAMX").count()
+impl From<&str> for Pattern {
+    fn from(template: &str) -> Self {
+        let cells = template
+            .lines()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .filter(|&(_, c)| c != '.')
+                    .map(move |(x, c)| {
+                        let pos = Pos {
+                            x: x as i64,
+                            y: y as i64,
+                        };
+                        (pos, c)
+                    })
+            })
+            .collect();
+        Pattern { cells }.normalized()
+    }
 }
 
-#[aoc(day4, part2)]
-fn part2(input: &Input) -> usize {
-    input[1..input.len() - 1].iter().enumerate().map(|(i, row)| {
-        (1..row.len() - 1)
-            .map(|j| {
-                check_x_mas(&[
-                    &input[i][j - 1..j + 2],
-                    &input[i + 1][j - 1..j + 2],
-                    &input[i + 2][j - 1..j + 2],
-                ])
+impl Pattern {
+    fn normalized(mut self) -> Self {
+        let min_x = self.cells.iter().map(|(pos, _)| pos.x).min().unwrap_or(0);
+        let min_y = self.cells.iter().map(|(pos, _)| pos.y).min().unwrap_or(0);
+        for (pos, _) in self.cells.iter_mut() {
+            pos.x -= min_x;
+            pos.y -= min_y;
+        }
+        self.cells.sort_unstable_by_key(|&(pos, _)| pos);
+        self
+    }
+
+    fn map(&self, transform: impl Fn(Pos) -> Pos) -> Self {
+        Pattern {
+            cells: self
+                .cells
+                .iter()
+                .map(|&(pos, c)| (transform(pos), c))
+                .collect(),
+        }
+        .normalized()
+    }
+
+    // The distinct rotations and reflections of the pattern.
+    pub fn variants(&self) -> Vec<Pattern> {
+        let rotations = (0..4).scan(self.clone(), |pattern, _| {
+            let current = pattern.clone();
+            *pattern = pattern.map(|pos| Pos {
+                x: -pos.y,
+                y: pos.x,
+            });
+            Some(current)
+        });
+
+        rotations
+            .flat_map(|pattern| {
+                let reflected = pattern.map(|pos| Pos {
+                    x: -pos.x,
+                    y: pos.y,
+                });
+                [pattern, reflected]
             })
-            .sum::<usize>()
-    }).sum()
+            .unique_by(|pattern| pattern.cells.clone())
+            .collect()
+    }
 }
 
-fn check_x_mas(input: &[&str]) -> usize {
-    if input[1].chars().nth(1) != Some('A') {
-        0
-    } else {
-        let x: Vec<_> = [0, 2]
-            .iter()
-            .cartesian_product([0, 2].iter())
-            .map(|(&i, &j)| input[i].chars().nth(j).unwrap())
-            .collect();
+impl From<&str> for WordSearch {
+    fn from(input: &str) -> Self {
+        WordSearch {
+            grid: input.lines().map(|line| line.chars().collect()).collect(),
+        }
+    }
+}
 
-        if is_ms(x[0], x[3]) && is_ms(x[1], x[2]) {
-            1
-        } else {
-            0
+impl WordSearch {
+    fn get(&self, pos: Pos) -> Option<char> {
+        let row = self.grid.get(usize::try_from(pos.y).ok()?)?;
+        row.get(usize::try_from(pos.x).ok()?).copied()
+    }
+
+    fn positions(&self) -> impl Iterator<Item = Pos> + '_ {
+        self.grid.iter().enumerate().flat_map(|(y, row)| {
+            (0..row.len()).map(move |x| Pos {
+                x: x as i64,
+                y: y as i64,
+            })
+        })
+    }
+
+    // Every occurrence of the word along rows, columns and diagonals, in both directions.
+    pub fn find_word(&self, word: &str) -> Vec<Match> {
+        let word = &word.chars().collect_vec();
+        if word.is_empty() {
+            return Vec::new();
         }
+        // A single letter reads the same in every direction.
+        let directions = if word.len() == 1 {
+            &DIRECTIONS[..1]
+        } else {
+            &DIRECTIONS[..]
+        };
+
+        self.positions()
+            .filter(|&pos| self.get(pos) == Some(word[0]))
+            .flat_map(|start| {
+                directions.iter().filter_map(move |&(dx, dy)| {
+                    let cells = (0..word.len() as i64)
+                        .map(|i| Pos {
+                            x: start.x + dx * i,
+                            y: start.y + dy * i,
+                        })
+                        .collect_vec();
+                    cells
+                        .iter()
+                        .zip(word)
+                        .all(|(&pos, &c)| self.get(pos) == Some(c))
+                        .then_some(Match { cells })
+                })
+            })
+            .collect()
+    }
+
+    // Every placement of any rotation or reflection of the pattern.
+    pub fn find_pattern(&self, pattern: &Pattern) -> Vec<Match> {
+        let variants = pattern.variants();
+
+        self.positions()
+            .flat_map(|origin| {
+                variants.iter().filter_map(move |variant| {
+                    let cells = variant
+                        .cells
+                        .iter()
+                        .map(|&(pos, c)| (origin + pos, c))
+                        .collect_vec();
+                    cells
+                        .iter()
+                        .all(|&(pos, c)| self.get(pos) == Some(c))
+                        .then(|| Match {
+                            cells: cells.into_iter().map(|(pos, _)| pos).collect(),
+                        })
+                })
+            })
+            .collect()
     }
 }
 
-fn is_ms(a: char, b: char) -> bool {
-    (a == 'M' && b == 'S') || (a == 'S' && b == 'M')
+#[aoc_generator(day4)]
+pub fn input_generator(input: &str) -> WordSearch {
+    WordSearch::from(input)
+}
+
+#[aoc(day4, part1)]
+fn part1(input: &WordSearch) -> usize {
+    input.find_word("XMAS").len()
+}
+
+#[aoc(day4, part2)]
+fn part2(input: &WordSearch) -> usize {
+    input.find_pattern(&Pattern::from("M.S\n.A.\nM.S")).len()
 }
 
 #[cfg(test)]
@@ -158,4 +235,36 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&input_generator("MMMSXXMASM\nMSAMXMSMSA\nAMXSXMAAMM\nMSAMASMSMX\nXMASAMXAMM\nXXAMMXXAMA\nSMSMSASXSS\nSAXAMASAAA\nMAMMMXMMMM\nMXMXAXMASX")), 9);
     }
+
+    #[test]
+    fn match_positions() {
+        let search = input_generator("..S\n.A.\nMXAS");
+        assert_eq!(
+            search.find_word("MAS"),
+            vec![Match {
+                cells: vec![Pos { x: 0, y: 2 }, Pos { x: 1, y: 1 }, Pos { x: 2, y: 0 }]
+            }]
+        );
+        assert_eq!(search.find_word("AS").len(), 2);
+        assert_eq!(search.find_word("SAX").len(), 1);
+        assert_eq!(input_generator("XA\nAX").find_word("A").len(), 2);
+    }
+
+    #[test]
+    fn pattern_variants() {
+        assert_eq!(Pattern::from("M.S\n.A.\nM.S").variants().len(), 4);
+        assert_eq!(Pattern::from("A").variants().len(), 1);
+        assert_eq!(Pattern::from("AB").variants().len(), 4);
+        assert_eq!(Pattern::from("AB\n.C").variants().len(), 8);
+
+        let search = input_generator("XAB\nXCX\nCXX");
+        assert_eq!(search.find_pattern(&Pattern::from("AB\nC.")).len(), 1);
+        let matches = search.find_pattern(&Pattern::from("BA\n.C"));
+        assert_eq!(
+            matches,
+            vec![Match {
+                cells: vec![Pos { x: 1, y: 0 }, Pos { x: 2, y: 0 }, Pos { x: 1, y: 1 }]
+            }]
+        );
+    }
 }
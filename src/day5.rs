@@ -1,50 +1,140 @@
 use std::{
     collections::{HashMap, HashSet},
-    rc::Rc,
+    fmt,
 };
 
 use itertools::Itertools;
 
-#[derive(Debug, Clone, PartialEq)]
-struct Input {
-    rules_inverse: HashMap<u32, Rc<HashSet<u32>>>,
-    updates: Vec<Vec<Page>>,
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RuleGraph {
+    successors: HashMap<u32, HashSet<u32>>,
 }
 
-#[derive(Debug, Clone)]
-struct Page {
-    number: u32,
-    predecessors: Rc<HashSet<u32>>,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderError {
+    // The pages form a cycle of rules, listed in rule order.
+    Cycle(Vec<u32>),
+    // No rule decides which of the two pages comes first.
+    Ambiguous(u32, u32),
 }
 
-impl PartialEq for Page {
-    fn eq(&self, other: &Self) -> bool {
-        self.number == other.number
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderError::Cycle(pages) => write!(f, "rules form a cycle: {}", pages.iter().join("|")),
+            OrderError::Ambiguous(a, b) => write!(f, "no rule orders pages {} and {}", a, b),
+        }
     }
 }
 
-impl Eq for Page {}
+impl std::error::Error for OrderError {}
 
-impl PartialOrd for Page {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        other
-            .predecessors
-            .contains(&self.number)
-            .then(|| std::cmp::Ordering::Less)
+impl RuleGraph {
+    pub fn add_rule(&mut self, before: u32, after: u32) {
+        self.successors.entry(before).or_default().insert(after);
     }
-}
 
-impl Ord for Page {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.partial_cmp(other).unwrap()
+    pub fn requires(&self, before: u32, after: u32) -> bool {
+        self.successors
+            .get(&before)
+            .is_some_and(|successors| successors.contains(&after))
+    }
+
+    // The first rule `before|after` broken by the update, if any.
+    pub fn violation(&self, update: &[u32]) -> Option<(u32, u32)> {
+        update
+            .iter()
+            .tuple_combinations()
+            .find(|&(&first, &second)| self.requires(second, first))
+            .map(|(&first, &second)| (second, first))
+    }
+
+    pub fn is_valid(&self, update: &[u32]) -> bool {
+        self.violation(update).is_none()
+    }
+
+    // Kahn's algorithm restricted to the pages of the update. The order is only accepted if it is
+    // the unique one, i.e. there is exactly one candidate at every step.
+    pub fn order(&self, update: &[u32]) -> Result<Vec<u32>, OrderError> {
+        let pages = update.iter().copied().collect::<HashSet<_>>();
+        let edges = |page: u32| {
+            pages
+                .iter()
+                .copied()
+                .filter(move |&other| self.requires(page, other))
+        };
+
+        let mut in_degree = pages
+            .iter()
+            .map(|&page| (page, 0))
+            .collect::<HashMap<_, _>>();
+        for &page in &pages {
+            for successor in edges(page) {
+                *in_degree.get_mut(&successor).unwrap() += 1;
+            }
+        }
+
+        let mut order = Vec::with_capacity(pages.len());
+        while order.len() < pages.len() {
+            let candidates = in_degree
+                .iter()
+                .filter(|&(_, &degree)| degree == 0)
+                .map(|(&page, _)| page)
+                .sorted()
+                .collect_vec();
+
+            match candidates[..] {
+                [] => return Err(OrderError::Cycle(self.find_cycle(&in_degree))),
+                [page] => {
+                    in_degree.remove(&page);
+                    for successor in edges(page) {
+                        *in_degree.get_mut(&successor).unwrap() -= 1;
+                    }
+                    order.push(page);
+                }
+                [a, b, ..] => return Err(OrderError::Ambiguous(a, b)),
+            }
+        }
+
+        Ok(order)
+    }
+
+    // Every remaining page has a remaining predecessor, so walking backwards must revisit a page.
+    fn find_cycle(&self, remaining: &HashMap<u32, usize>) -> Vec<u32> {
+        let predecessor = |page: u32| {
+            remaining
+                .keys()
+                .copied()
+                .filter(|&other| self.requires(other, page))
+                .min()
+                .unwrap()
+        };
+
+        let mut path = vec![*remaining.keys().min().unwrap()];
+        loop {
+            let next = predecessor(*path.last().unwrap());
+            if let Some(start) = path.iter().position(|&page| page == next) {
+                let mut cycle = path.split_off(start);
+                cycle.reverse();
+                let first = cycle.iter().position_min().unwrap();
+                cycle.rotate_left(first);
+                return cycle;
+            }
+            path.push(next);
+        }
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+struct Input {
+    rules: RuleGraph,
+    updates: Vec<Vec<u32>>,
+}
+
 #[aoc_generator(day5)]
 fn input_generator(input: &str) -> Input {
     let mut lines = input.lines();
-
-    let mut rules_inverse: HashMap<u32, HashSet<u32>> = HashMap::new();
+    let mut rules = RuleGraph::default();
 
     lines
         .by_ref()
@@ -55,39 +145,14 @@ fn input_generator(input: &str) -> Input {
                 .map(|s| s.parse().unwrap())
                 .collect_tuple()
                 .unwrap();
-            if rules_inverse.contains_key(&right) {
-                rules_inverse.get_mut(&right).unwrap().insert(left);
-            } else {
-                rules_inverse.insert(right, HashSet::from([left]));
-            }
-
-            if !rules_inverse.contains_key(&left) {
-                rules_inverse.insert(left, HashSet::new());
-            }
+            rules.add_rule(left, right);
         });
 
-    let rules_inverse: HashMap<u32, Rc<HashSet<u32>>> = rules_inverse
-        .into_iter()
-        .map(|(k, v)| (k, Rc::new(v)))
+    let updates = lines
+        .map(|line| line.split(',').map(|s| s.parse().unwrap()).collect())
         .collect();
 
-    let mut updates = vec![];
-    lines.for_each(|line| {
-        updates.push(
-            line.split(',')
-                .map(|s| s.parse().unwrap())
-                .map(|number| Page {
-                    number,
-                    predecessors: rules_inverse.get(&number).unwrap().clone(),
-                })
-                .collect(),
-        );
-    });
-
-    Input {
-        rules_inverse,
-        updates,
-    }
+    Input { rules, updates }
 }
 
 #[aoc(day5, part1)]
@@ -95,19 +160,19 @@ fn part1(input: &Input) -> u32 {
     input
         .updates
         .iter()
-        .filter(|updates| updates.iter().is_sorted())
-        .map(|updates| updates[updates.len() / 2].number)
+        .filter(|update| input.rules.is_valid(update))
+        .map(|update| update[update.len() / 2])
         .sum()
 }
 
 #[aoc(day5, part2)]
-fn part2(input: &Input) -> u32 {
+fn part2(input: &Input) -> Result<u32, OrderError> {
     input
         .updates
         .iter()
-        .filter(|updates| !updates.iter().is_sorted())
-        .map(|updates| updates.iter().sorted().collect::<Vec<_>>())
-        .map(|updates| updates[updates.len() / 2].number)
+        .filter(|update| !input.rules.is_valid(update))
+        .map(|update| input.rules.order(update))
+        .map_ok(|update| update[update.len() / 2])
         .sum()
 }
 
@@ -117,24 +182,14 @@ mod tests {
 
     #[test]
     fn input_test() {
-        let rc1 = Rc::new(HashSet::from([2]));
-        let rc2 = Rc::new(HashSet::from([1]));
-        let rc1_clone = rc1.clone();
-        let rc2_clone = rc2.clone();
+        let mut rules = RuleGraph::default();
+        rules.add_rule(1, 2);
+        rules.add_rule(2, 1);
         assert_eq!(
             input_generator("1|2\n2|1\n\n1,2"),
             Input {
-                rules_inverse: vec![(2, rc2), (1, rc1)].into_iter().collect(),
-                updates: vec![vec![
-                    Page {
-                        number: 1,
-                        predecessors: rc1_clone
-                    },
-                    Page {
-                        number: 2,
-                        predecessors: rc2_clone
-                    },
-                ]]
+                rules,
+                updates: vec![vec![1, 2]]
             }
         )
     }
@@ -148,6 +203,61 @@ mod tests {
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&input_generator(EXAMPLE_INPUT)), 123);
+        assert_eq!(part2(&input_generator(EXAMPLE_INPUT)), Ok(123));
+    }
+
+    #[test]
+    fn test_violations() {
+        let input = input_generator(EXAMPLE_INPUT);
+        let violations = input
+            .updates
+            .iter()
+            .map(|update| input.rules.violation(update))
+            .collect_vec();
+        assert_eq!(
+            violations,
+            vec![
+                None,
+                None,
+                None,
+                Some((97, 75)),
+                Some((29, 13)),
+                Some((75, 13))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_order() {
+        let input = input_generator(EXAMPLE_INPUT);
+        assert_eq!(
+            input.rules.order(&[97, 13, 75, 29, 47]),
+            Ok(vec![97, 75, 47, 29, 13])
+        );
+        assert_eq!(input.rules.order(&[]), Ok(vec![]));
+
+        let rules = input_generator("1|2\n2|3\n3|1\n3|4\n\n1").rules;
+        assert_eq!(
+            rules.order(&[4, 3, 2, 1]),
+            Err(OrderError::Cycle(vec![1, 2, 3]))
+        );
+        assert_eq!(rules.order(&[1, 2]), Ok(vec![1, 2]));
+
+        let rules = input_generator("1|2\n1|3\n\n1").rules;
+        assert_eq!(rules.order(&[3, 2, 1]), Err(OrderError::Ambiguous(2, 3)));
+        assert_eq!(rules.violation(&[3, 2, 1]), Some((1, 3)));
+    }
+
+    #[test]
+    fn part2_reports_unorderable_updates() {
+        let input = input_generator("1|2\n2|3\n3|1\n\n1,2\n3,2,1");
+        assert_eq!(part2(&input), Err(OrderError::Cycle(vec![1, 2, 3])));
+        assert_eq!(
+            part2(&input).unwrap_err().to_string(),
+            "rules form a cycle: 1|2|3"
+        );
+
+        let input = input_generator("1|2\n1|3\n\n3,2,1");
+        assert_eq!(part2(&input), Err(OrderError::Ambiguous(2, 3)));
     }
 }
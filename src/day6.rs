@@ -1,10 +1,14 @@
 use std::{
-    collections::HashSet,
+    borrow::Cow,
+    collections::HashMap,
     ops::{Add, AddAssign, Index, IndexMut},
     vec,
 };
 
-use itertools::Itertools;
+use crate::{
+    utils,
+    visualiser::{Frame, Simulation},
+};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 enum Direction {
@@ -33,9 +37,9 @@ struct Pos {
     dir: Direction,
 }
 
-impl Pos {
-    fn turn_right(&mut self) {
-        self.dir = match self.dir {
+impl Direction {
+    fn turn_right(self) -> Self {
+        match self {
             Direction::Up => Direction::Right,
             Direction::Right => Direction::Down,
             Direction::Down => Direction::Left,
@@ -43,8 +47,18 @@ impl Pos {
         }
     }
 
-    fn equals_without_dir(&self, other: &Pos) -> bool {
-        self.x == other.x && self.y == other.y
+    fn reverse(self) -> Self {
+        self.turn_right().turn_right()
+    }
+
+    fn turn_left(self) -> Self {
+        self.reverse().turn_right()
+    }
+}
+
+impl Pos {
+    fn turn_right(&mut self) {
+        self.dir = self.dir.turn_right();
     }
 }
 
@@ -112,6 +126,7 @@ impl From<char> for LabField {
 struct LabMap {
     fields: Vec<Vec<LabField>>,
     guard_pos: Pos,
+    guards: Vec<Pos>,
     width: usize,
     height: usize,
 }
@@ -128,7 +143,14 @@ impl<'a> FromIterator<&'a str> for LabMap {
                 .enumerate()
                 .for_each(|(x, c)| match LabField::from(c) {
                     LabField::Guard => {
-                        lab_map.place_guard(x as isize, y as isize, Direction::from(c));
+                        if lab_map.guards.is_empty() {
+                            lab_map.place_guard(x as isize, y as isize, Direction::from(c));
+                        }
+                        lab_map.guards.push(Pos {
+                            x: x as isize,
+                            y: y as isize,
+                            dir: Direction::from(c),
+                        });
                         lab_map.fields[y].push(LabField::Guard);
                     }
                     pos => lab_map.fields[lab_map.height - 1].push(pos),
@@ -164,6 +186,7 @@ impl LabMap {
                 y: 0,
                 dir: Direction::Right,
             },
+            guards: vec![],
             width: 0,
             height: 0,
         }
@@ -187,10 +210,11 @@ impl LabMap {
     }
 
     fn out_of_bounds(&self) -> bool {
-        self.guard_pos.x >= self.width as isize
-            || self.guard_pos.y >= self.height as isize
-            || self.guard_pos.x < 0
-            || self.guard_pos.y < 0
+        !self.contains(self.guard_pos)
+    }
+
+    fn contains(&self, pos: Pos) -> bool {
+        pos.x >= 0 && pos.y >= 0 && pos.x < self.width as isize && pos.y < self.height as isize
     }
 }
 
@@ -211,57 +235,429 @@ fn input_generator(input: &str) -> LabMap {
     input.lines().collect()
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TurnPolicy {
+    Right,
+    Left,
+    Alternating,
+    Reverse,
+}
+
+impl TurnPolicy {
+    fn turn(self, dir: Direction, turns_taken: usize) -> Direction {
+        match self {
+            TurnPolicy::Right => dir.turn_right(),
+            TurnPolicy::Left => dir.turn_left(),
+            TurnPolicy::Alternating if turns_taken.is_multiple_of(2) => dir.turn_right(),
+            TurnPolicy::Alternating => dir.turn_left(),
+            TurnPolicy::Reverse => dir.reverse(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Guard {
+    pos: Pos,
+    policy: TurnPolicy,
+    turns_taken: usize,
+    coverage: Vec<bool>,
+    on_patrol: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PatrolOutcome {
+    AllLeft { steps: usize },
+    Collision { step: usize, guards: (usize, usize) },
+    Loop { start: usize, period: usize },
+}
+
+// Ticks all guards in lockstep. In one tick each guard either turns in place or moves one cell.
+pub struct Patrol<'a> {
+    lab_map: Cow<'a, LabMap>,
+    guards: Vec<Guard>,
+}
+
+impl Patrol<'static> {
+    // Guards take their turn policies from `policies` in the order they appear on the map,
+    // cycling through the list if there are more guards than policies.
+    pub fn from_input(input: &str, policies: &[TurnPolicy]) -> Self {
+        Patrol::with_map(Cow::Owned(input_generator(input)), policies)
+    }
+}
+
+impl<'a> Patrol<'a> {
+    fn new(lab_map: &'a LabMap, policies: &[TurnPolicy]) -> Self {
+        Self::with_map(Cow::Borrowed(lab_map), policies)
+    }
+
+    fn with_map(lab_map: Cow<'a, LabMap>, policies: &[TurnPolicy]) -> Self {
+        let guards = lab_map
+            .guards
+            .iter()
+            .zip(policies.iter().cycle())
+            .map(|(&pos, &policy)| {
+                let mut coverage = vec![false; lab_map.width * lab_map.height];
+                coverage[pos.y as usize * lab_map.width + pos.x as usize] = true;
+                Guard {
+                    pos,
+                    policy,
+                    turns_taken: 0,
+                    coverage,
+                    on_patrol: true,
+                }
+            })
+            .collect();
+
+        Patrol { lab_map, guards }
+    }
+
+    fn tick(&mut self) {
+        for guard in self.guards.iter_mut().filter(|guard| guard.on_patrol) {
+            let next = guard.pos + &guard.pos.dir;
+            if !self.lab_map.contains(next) {
+                guard.on_patrol = false;
+            } else if self.lab_map[next] == LabField::Obstacle {
+                guard.pos.dir = guard.policy.turn(guard.pos.dir, guard.turns_taken);
+                guard.turns_taken += 1;
+            } else {
+                guard.pos = next;
+                guard.coverage[next.y as usize * self.lab_map.width + next.x as usize] = true;
+            }
+        }
+    }
+
+    fn collision(&self, previous: &[Pos]) -> Option<(usize, usize)> {
+        let active = self
+            .guards
+            .iter()
+            .enumerate()
+            .filter(|(_, guard)| guard.on_patrol)
+            .collect::<Vec<_>>();
+
+        for (i, &(a, guard_a)) in active.iter().enumerate() {
+            for &(b, guard_b) in &active[i + 1..] {
+                let (pa, pb) = (guard_a.pos, guard_b.pos);
+                let same_cell = pa.x == pb.x && pa.y == pb.y;
+                let swapped = (pa.x, pa.y) == (previous[b].x, previous[b].y)
+                    && (pb.x, pb.y) == (previous[a].x, previous[a].y);
+                if same_cell || swapped {
+                    return Some((a, b));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn state(&self) -> Vec<Option<(Pos, usize)>> {
+        self.guards
+            .iter()
+            .map(|guard| {
+                let phase = match guard.policy {
+                    TurnPolicy::Alternating => guard.turns_taken % 2,
+                    _ => 0,
+                };
+                guard.on_patrol.then_some((guard.pos, phase))
+            })
+            .collect()
+    }
+
+    pub fn run(&mut self) -> PatrolOutcome {
+        let mut seen = HashMap::new();
+        (0..)
+            .find_map(|step| self.advance(step, &mut seen))
+            .unwrap()
+    }
+
+    // Checks the state before tick `step` and performs the tick, returning the outcome once the
+    // patrol is over.
+    fn advance(
+        &mut self,
+        step: usize,
+        seen: &mut HashMap<Vec<Option<(Pos, usize)>>, usize>,
+    ) -> Option<PatrolOutcome> {
+        if self.guards.iter().all(|guard| !guard.on_patrol) {
+            return Some(PatrolOutcome::AllLeft { steps: step });
+        }
+        if let Some(start) = seen.insert(self.state(), step) {
+            return Some(PatrolOutcome::Loop {
+                start,
+                period: step - start,
+            });
+        }
+
+        let previous = self
+            .guards
+            .iter()
+            .map(|guard| guard.pos)
+            .collect::<Vec<_>>();
+        self.tick();
+
+        self.collision(&previous)
+            .map(|guards| PatrolOutcome::Collision {
+                step: step + 1,
+                guards,
+            })
+    }
+
+    // The cells the guard has visited, indexed row by row.
+    pub fn coverage(&self, guard: usize) -> &[bool] {
+        &self.guards[guard].coverage
+    }
+
+    pub fn covered_cells(&self) -> usize {
+        (0..self.lab_map.width * self.lab_map.height)
+            .filter(|&idx| (0..self.guards.len()).any(|guard| self.coverage(guard)[idx]))
+            .count()
+    }
+}
+
 #[aoc(day6, part1)]
 fn part1(input: &LabMap) -> usize {
-    let mut lab_map = input.clone();
+    let mut patrol = Patrol::new(input, &[TurnPolicy::Right]);
+    patrol.run();
+    patrol.covered_cells()
+}
 
-    while let Some(_) = lab_map.next() {}
+struct PatrolSimulation {
+    patrol: Patrol<'static>,
+    step: usize,
+    seen: HashMap<Vec<Option<(Pos, usize)>>, usize>,
+    outcome: Option<PatrolOutcome>,
+}
 
-    lab_map
-        .fields
-        .iter()
-        .flatten()
-        .filter(|pos| pos == &&LabField::Patrolled)
-        .count()
+pub fn simulation(input: &str) -> impl Simulation {
+    PatrolSimulation {
+        patrol: Patrol::from_input(input, &[TurnPolicy::Right]),
+        step: 0,
+        seen: HashMap::new(),
+        outcome: None,
+    }
 }
 
-#[aoc(day6, part2)]
-fn part2(input: &LabMap) -> usize {
-    let lab_map = input.clone();
-
-    let mut obstacle_placements = HashSet::<Pos>::new();
-    obstacle_placements.insert(lab_map.guard_pos);
-    let guard_route = lab_map.collect_vec();
-
-    for i in 0..guard_route.len() - 2 {
-        let mut lab_map = input.clone();
-        let mut current_guard_route = guard_route[..=i].to_vec();
-        if input[guard_route[i + 1]] == LabField::Obstacle
-            || current_guard_route
-                .iter()
-                .any(|pos| pos.equals_without_dir(&guard_route[i + 1]))
-        {
-            continue;
+impl Simulation for PatrolSimulation {
+    fn frame(&self) -> Frame {
+        let lab_map = &self.patrol.lab_map;
+        let mut rows = lab_map
+            .fields
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&field| match field {
+                        LabField::Obstacle => '#',
+                        _ => '.',
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        for guard in &self.patrol.guards {
+            for (idx, _) in guard.coverage.iter().enumerate().filter(|(_, &c)| c) {
+                rows[idx / lab_map.width][idx % lab_map.width] = 'X';
+            }
         }
-        lab_map.guard_pos = guard_route[i];
-        lab_map[guard_route[i + 1]] = LabField::Obstacle;
-        lab_map.move_guard();
+        for guard in self.patrol.guards.iter().filter(|guard| guard.on_patrol) {
+            rows[guard.pos.y as usize][guard.pos.x as usize] = match guard.pos.dir {
+                Direction::Up => '^',
+                Direction::Down => 'v',
+                Direction::Left => '<',
+                Direction::Right => '>',
+            };
+        }
+
+        Frame::from_grid(
+            &rows,
+            |&c| c,
+            format!(
+                "covered cells: {}, outcome: {:?}",
+                self.patrol.covered_cells(),
+                self.outcome
+            ),
+        )
+    }
 
-        while !lab_map.out_of_bounds() && !current_guard_route.contains(&lab_map.guard_pos) {
-            current_guard_route.push(lab_map.guard_pos);
-            lab_map.move_guard();
+    fn step(&mut self) -> bool {
+        if self.outcome.is_some() {
+            return false;
         }
+        self.outcome = self.patrol.advance(self.step, &mut self.seen);
+        self.step += 1;
+        true
+    }
+}
+
+const DELTAS: [(isize, isize); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+fn dir_index(dir: Direction) -> usize {
+    match dir {
+        Direction::Up => 0,
+        Direction::Right => 1,
+        Direction::Down => 2,
+        Direction::Left => 3,
+    }
+}
+
+struct JumpTable {
+    width: usize,
+    // stops[idx * 4 + dir] is the last cell the guard reaches when walking from idx in dir,
+    // or None if it leaves the lab before hitting an obstacle.
+    stops: Vec<Option<usize>>,
+}
+
+impl JumpTable {
+    fn new(lab_map: &LabMap) -> Self {
+        let (width, height) = (lab_map.width, lab_map.height);
+        let obstacle = |x: usize, y: usize| lab_map.fields[y][x] == LabField::Obstacle;
+        let mut stops = vec![None; width * height * 4];
+
+        for y in 0..height {
+            for x in 0..width {
+                let idx = y * width + x;
+                stops[idx * 4] = match y {
+                    0 => None,
+                    _ if obstacle(x, y - 1) => Some(idx),
+                    _ => stops[(idx - width) * 4],
+                };
+                stops[idx * 4 + 3] = match x {
+                    0 => None,
+                    _ if obstacle(x - 1, y) => Some(idx),
+                    _ => stops[(idx - 1) * 4 + 3],
+                };
+            }
+        }
+
+        for y in (0..height).rev() {
+            for x in (0..width).rev() {
+                let idx = y * width + x;
+                stops[idx * 4 + 2] = match y + 1 {
+                    next if next == height => None,
+                    next if obstacle(x, next) => Some(idx),
+                    _ => stops[(idx + width) * 4 + 2],
+                };
+                stops[idx * 4 + 1] = match x + 1 {
+                    next if next == width => None,
+                    next if obstacle(next, y) => Some(idx),
+                    _ => stops[(idx + 1) * 4 + 1],
+                };
+            }
+        }
+
+        JumpTable { width, stops }
+    }
+
+    fn coords(&self, idx: usize) -> (isize, isize) {
+        ((idx % self.width) as isize, (idx / self.width) as isize)
+    }
+
+    // Number of steps from `from` to `to` when walking in `dir`, if `to` lies ahead of `from`.
+    fn distance_ahead(&self, from: usize, to: usize, dir: usize) -> Option<isize> {
+        let ((fx, fy), (tx, ty)) = (self.coords(from), self.coords(to));
+        let (dx, dy) = DELTAS[dir];
+        let steps = if dx != 0 {
+            (tx - fx) * dx
+        } else {
+            (ty - fy) * dy
+        };
+        let aligned = if dx != 0 { fy == ty } else { fx == tx };
+        (aligned && steps > 0).then_some(steps)
+    }
+
+    fn walk(&self, from: usize, dir: usize, steps: isize) -> usize {
+        let (x, y) = self.coords(from);
+        let (dx, dy) = DELTAS[dir];
+        ((y + dy * steps) * self.width as isize + x + dx * steps) as usize
+    }
+
+    fn loops_with_obstacle(
+        &self,
+        start: usize,
+        start_dir: usize,
+        obstacle: usize,
+        visited: &mut [u32],
+        stamp: u32,
+    ) -> bool {
+        let (mut pos, mut dir) = (start, start_dir);
+
+        loop {
+            let mut stop = self.stops[pos * 4 + dir];
+            if let Some(to_obstacle) = self.distance_ahead(pos, obstacle, dir) {
+                let to_stop = stop.map_or(isize::MAX, |stop| {
+                    self.distance_ahead(pos, stop, dir).unwrap_or(0)
+                });
+                if to_obstacle <= to_stop {
+                    stop = Some(self.walk(pos, dir, to_obstacle - 1));
+                }
+            }
+
+            let Some(stop) = stop else {
+                return false;
+            };
+
+            let key = stop * 4 + dir;
+            if visited[key] == stamp {
+                return true;
+            }
+            visited[key] = stamp;
+
+            pos = stop;
+            dir = (dir + 1) % 4;
+        }
+    }
+}
+
+fn obstacle_candidates(input: &LabMap) -> Vec<utils::Pos> {
+    let jump_table = JumpTable::new(input);
+    let width = input.width;
+    let index = |pos: Pos| pos.y as usize * width + pos.x as usize;
 
-        if !lab_map.out_of_bounds() {
-            obstacle_placements.insert(guard_route[i + 1]);
+    // The state the guard is in right before stepping onto each cell for the first time.
+    let mut first_entry = vec![None; input.width * input.height];
+    let mut lab_map = input.clone();
+    let start = index(input.guard_pos);
+    let mut prev = lab_map.guard_pos;
+    let mut walked = vec![false; input.width * input.height * 4];
+    while let Some(pos) = lab_map.next() {
+        if lab_map.out_of_bounds() {
+            break;
+        }
+        let idx = index(pos);
+        let state = idx * 4 + dir_index(pos.dir);
+        if walked[state] {
+            break;
+        }
+        walked[state] = true;
+        if idx != start && idx != index(prev) && first_entry[idx].is_none() {
+            first_entry[idx] = Some((index(prev), dir_index(prev.dir)));
         }
+        prev = pos;
     }
 
-    obstacle_placements.len() - 1
+    let mut visited = vec![0; input.width * input.height * 4];
+
+    first_entry
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, entry)| entry.map(|entry| (idx, entry)))
+        .enumerate()
+        .filter(|&(i, (obstacle, (pos, dir)))| {
+            jump_table.loops_with_obstacle(pos, dir, obstacle, &mut visited, i as u32 + 1)
+        })
+        .map(|(_, (obstacle, _))| utils::Pos {
+            x: (obstacle % width) as i64,
+            y: (obstacle / width) as i64,
+        })
+        .collect()
+}
+
+#[aoc(day6, part2)]
+fn part2(input: &LabMap) -> usize {
+    obstacle_candidates(input).len()
 }
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use super::*;
 
     const EXAMPLE_INPUT: &str = "....#.....\n.........#\n..........\n..#.......\n.......#..\n..........\n.#..^.....\n........#.\n#.........\n......#...";
@@ -291,4 +687,89 @@ mod tests {
     fn test_part2() {
         assert_eq!(part2(&input_generator(EXAMPLE_INPUT)), 6);
     }
+
+    #[test]
+    fn test_turn_policies() {
+        let lab_map = input_generator(EXAMPLE_INPUT);
+
+        let mut patrol = Patrol::new(&lab_map, &[TurnPolicy::Right]);
+        assert!(matches!(patrol.run(), PatrolOutcome::AllLeft { .. }));
+        assert_eq!(patrol.coverage(0).iter().filter(|&&c| c).count(), 41);
+
+        let mut patrol = Patrol::new(&lab_map, &[TurnPolicy::Reverse]);
+        assert_eq!(patrol.run(), PatrolOutcome::AllLeft { steps: 15 });
+        assert_eq!(patrol.covered_cells(), 9);
+
+        let lab_map = input_generator(".#...\n....#\n#^...\n...#.");
+        let outcome = |policy| Patrol::new(&lab_map, &[policy]).run();
+        assert_eq!(
+            outcome(TurnPolicy::Right),
+            PatrolOutcome::Loop {
+                start: 0,
+                period: 10
+            }
+        );
+        assert_eq!(
+            outcome(TurnPolicy::Left),
+            PatrolOutcome::AllLeft { steps: 4 }
+        );
+        assert_eq!(
+            outcome(TurnPolicy::Alternating),
+            PatrolOutcome::AllLeft { steps: 7 }
+        );
+        assert_eq!(
+            outcome(TurnPolicy::Reverse),
+            PatrolOutcome::AllLeft { steps: 5 }
+        );
+    }
+
+    #[test]
+    fn test_multiple_guards() {
+        let lab_map = input_generator(".>..<.");
+        let mut patrol = Patrol::new(&lab_map, &[TurnPolicy::Right]);
+        assert_eq!(
+            patrol.run(),
+            PatrolOutcome::Collision {
+                step: 2,
+                guards: (0, 1)
+            }
+        );
+
+        let lab_map = input_generator(".>.<.");
+        let mut patrol = Patrol::new(&lab_map, &[TurnPolicy::Right]);
+        assert_eq!(
+            patrol.run(),
+            PatrolOutcome::Collision {
+                step: 1,
+                guards: (0, 1)
+            }
+        );
+
+        let mut patrol = Patrol::from_input(
+            ".#......\n....#...\n#^......\n...#....\n........\n#..>...#",
+            &[TurnPolicy::Right, TurnPolicy::Reverse],
+        );
+        assert_eq!(
+            patrol.run(),
+            PatrolOutcome::Loop {
+                start: 0,
+                period: 60
+            }
+        );
+        assert_eq!(patrol.coverage(0).iter().filter(|&&c| c).count(), 6);
+        assert_eq!(patrol.coverage(1).iter().filter(|&&c| c).count(), 6);
+        assert_eq!(patrol.covered_cells(), 12);
+    }
+
+    #[test]
+    fn test_obstacle_candidates() {
+        assert_eq!(
+            obstacle_candidates(&input_generator(EXAMPLE_INPUT)),
+            [(3, 6), (6, 7), (3, 8), (1, 8), (7, 7), (7, 9)]
+                .map(utils::Pos::from)
+                .into_iter()
+                .sorted()
+                .collect_vec()
+        );
+    }
 }
@@ -1,6 +1,263 @@
-use std::{fmt::{Debug, Display}, ops::{Add, Mul}, str::FromStr};
+use std::collections::HashMap;
 
-type Equation = (u64, Vec<u64>);
+use itertools::Itertools;
+
+pub trait Operator {
+    fn symbol(&self) -> &str;
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64>;
+
+    // Every left operand for which `apply(left, right) == Some(result)`, or `None` if they cannot
+    // be listed cheaply. The solver then evaluates the remaining operands forwards instead.
+    fn undo(&self, result: u64, right: u64) -> Option<Vec<u64>>;
+}
+
+pub struct Add;
+
+impl Operator for Add {
+    fn symbol(&self) -> &str {
+        "+"
+    }
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        left.checked_add(right)
+    }
+
+    fn undo(&self, result: u64, right: u64) -> Option<Vec<u64>> {
+        Some(result.checked_sub(right).into_iter().collect())
+    }
+}
+
+pub struct Mul;
+
+impl Operator for Mul {
+    fn symbol(&self) -> &str {
+        "*"
+    }
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        left.checked_mul(right)
+    }
+
+    fn undo(&self, result: u64, right: u64) -> Option<Vec<u64>> {
+        match right {
+            0 if result == 0 => None,
+            0 => Some(vec![]),
+            _ => Some(
+                result
+                    .is_multiple_of(right)
+                    .then_some(result / right)
+                    .into_iter()
+                    .collect(),
+            ),
+        }
+    }
+}
+
+pub struct Concat;
+
+fn decimal_shift(n: u64) -> Option<u64> {
+    10u64.checked_pow(n.checked_ilog10().unwrap_or(0) + 1)
+}
+
+impl Operator for Concat {
+    fn symbol(&self) -> &str {
+        "||"
+    }
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        left.checked_mul(decimal_shift(right)?)?.checked_add(right)
+    }
+
+    fn undo(&self, result: u64, right: u64) -> Option<Vec<u64>> {
+        let left = result
+            .checked_sub(right)
+            .zip(decimal_shift(right))
+            .filter(|&(prefix, shift)| prefix.is_multiple_of(shift))
+            .map(|(prefix, shift)| prefix / shift);
+        Some(left.into_iter().collect())
+    }
+}
+
+pub struct Sub;
+
+impl Operator for Sub {
+    fn symbol(&self) -> &str {
+        "-"
+    }
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        left.checked_sub(right)
+    }
+
+    fn undo(&self, result: u64, right: u64) -> Option<Vec<u64>> {
+        Some(result.checked_add(right).into_iter().collect())
+    }
+}
+
+pub struct Pow;
+
+impl Operator for Pow {
+    fn symbol(&self) -> &str {
+        "^"
+    }
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        left.checked_pow(right.try_into().ok()?)
+    }
+
+    // Binary searches for the largest left operand whose power does not exceed the result.
+    fn undo(&self, result: u64, right: u64) -> Option<Vec<u64>> {
+        match right {
+            0 => return None,
+            1 => return Some(vec![result]),
+            _ => {}
+        }
+        let exp = u32::try_from(right).ok()?;
+        let (mut low, mut high) = (0, result);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            match mid.checked_pow(exp) {
+                Some(power) if power <= result => low = mid,
+                _ => high = mid - 1,
+            }
+        }
+        Some(
+            (low.checked_pow(exp) == Some(result))
+                .then_some(low)
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+pub struct BitAnd;
+
+impl Operator for BitAnd {
+    fn symbol(&self) -> &str {
+        "&"
+    }
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        Some(left & right)
+    }
+
+    fn undo(&self, result: u64, right: u64) -> Option<Vec<u64>> {
+        (result & !right != 0).then(Vec::new)
+    }
+}
+
+pub struct BitOr;
+
+// Above this many bits in the right operand there are too many left operands to list.
+const MAX_OR_UNDO_BITS: u32 = 8;
+
+impl Operator for BitOr {
+    fn symbol(&self) -> &str {
+        "|"
+    }
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        Some(left | right)
+    }
+
+    // The left operand holds every bit only the result has, plus any subset of the right's bits.
+    fn undo(&self, result: u64, right: u64) -> Option<Vec<u64>> {
+        if right & !result != 0 {
+            return Some(vec![]);
+        }
+        if right.count_ones() > MAX_OR_UNDO_BITS {
+            return None;
+        }
+        let required = result & !right;
+        let mut lefts = vec![required];
+        let mut subset = right;
+        while subset != 0 {
+            lefts.push(required | subset);
+            subset = (subset - 1) & right;
+        }
+        Some(lefts)
+    }
+}
+
+pub struct BitXor;
+
+impl Operator for BitXor {
+    fn symbol(&self) -> &str {
+        "xor"
+    }
+
+    fn apply(&self, left: u64, right: u64) -> Option<u64> {
+        Some(left ^ right)
+    }
+
+    fn undo(&self, result: u64, right: u64) -> Option<Vec<u64>> {
+        Some(vec![result ^ right])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Equation {
+    pub target: u64,
+    pub operands: Vec<u64>,
+}
+
+impl Equation {
+    // Operators are evaluated strictly left to right, e.g. `81 + 40 * 27` is `(81 + 40) * 27`.
+    pub fn witness(&self, ops: &[&dyn Operator]) -> Option<String> {
+        let chosen = solve(self.target, &self.operands, ops)?;
+        let mut expression = self.operands[0].to_string();
+        for (op, operand) in chosen.into_iter().zip(&self.operands[1..]) {
+            expression += &format!(" {} {}", ops[op].symbol(), operand);
+        }
+        Some(expression)
+    }
+}
+
+// Works backwards from the target, undoing the last operator at each step.
+fn solve(target: u64, operands: &[u64], ops: &[&dyn Operator]) -> Option<Vec<usize>> {
+    let (&last, rest) = operands.split_last()?;
+    if rest.is_empty() {
+        return (target == last).then(Vec::new);
+    }
+
+    for (i, op) in ops.iter().enumerate() {
+        let chosen = match op.undo(target, last) {
+            Some(lefts) => lefts.into_iter().find_map(|left| solve(left, rest, ops)),
+            None => evaluations(rest, ops)
+                .into_iter()
+                .find_map(|(value, chosen)| {
+                    (op.apply(value, last) == Some(target)).then_some(chosen)
+                }),
+        };
+        if let Some(mut chosen) = chosen {
+            chosen.push(i);
+            return Some(chosen);
+        }
+    }
+
+    None
+}
+
+// Every value the operands can evaluate to, each with one choice of operators.
+fn evaluations(operands: &[u64], ops: &[&dyn Operator]) -> HashMap<u64, Vec<usize>> {
+    let mut values = HashMap::from([(operands[0], vec![])]);
+
+    for &operand in &operands[1..] {
+        let mut next = HashMap::new();
+        for (value, chosen) in values {
+            for (i, op) in ops.iter().enumerate() {
+                if let Some(result) = op.apply(value, operand) {
+                    next.entry(result)
+                        .or_insert_with(|| chosen.iter().copied().chain([i]).collect_vec());
+                }
+            }
+        }
+        values = next;
+    }
+
+    values
+}
 
 #[aoc_generator(day7)]
 fn input_generator(input: &str) -> Vec<Equation> {
@@ -8,57 +265,32 @@ fn input_generator(input: &str) -> Vec<Equation> {
         .lines()
         .map(|line| {
             let parts: Vec<&str> = line.split(": ").collect();
-            let res = parts[0].parse().unwrap();
-            let args = parts[1]
+            let target = parts[0].parse().unwrap();
+            let operands = parts[1]
                 .split_whitespace()
                 .map(|arg| arg.parse().unwrap())
                 .collect();
-            (res, args)
+            Equation { target, operands }
         })
         .collect()
 }
 
-fn is_valid<T, F>(rhs: T, lhs: T, args: &[T], ops: &[F]) -> bool
-where
-    T: PartialOrd + Copy,
-    T: Add<Output = T> + Mul<Output = T>,
-    F: Fn(T, T) -> T,
-{
-    if args.is_empty() && rhs == lhs {
-        true
-    } else if args.is_empty() || rhs > lhs {
-        false
-    } else {
-        let remaining = &args[1..];
-        ops.iter()
-            .any(|op| is_valid(op(rhs, args[0]), lhs, remaining, ops))
-    }
-}
-
-#[aoc(day7, part1)]
-fn part1(input: &Vec<Equation>) -> u64 {
+fn calibration_result(input: &[Equation], ops: &[&dyn Operator]) -> u64 {
     input
         .iter()
-        .filter(|(lhs, args)| is_valid(0, *lhs, args, &vec![Add::add, Mul::mul]))
-        .map(|(lhs, _)| *lhs)
+        .filter(|equation| equation.witness(ops).is_some())
+        .map(|equation| equation.target)
         .sum()
 }
 
-#[aoc(day7, part2)]
-fn part2(input: &Vec<Equation>) -> u64 {
-    input
-        .iter()
-        .filter(|(lhs, args)| is_valid(0, *lhs, args, &vec![Add::add, Mul::mul, concat]))
-        .map(|(lhs, _)| *lhs)
-        .sum()
+#[aoc(day7, part1)]
+fn part1(input: &[Equation]) -> u64 {
+    calibration_result(input, &[&Add, &Mul])
 }
 
-fn concat<T>(left: T, right: T) -> T
-where
-    T: Display + FromStr,
-    <T as FromStr>::Err: Debug
-{
-    format!("{}{}", left, right).parse().unwrap()
+#[aoc(day7, part2)]
+fn part2(input: &[Equation]) -> u64 {
+    calibration_result(input, &[&Add, &Mul, &Concat])
 }
 
 #[cfg(test)]
@@ -81,4 +313,94 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&input_generator(EXAMPLE_INPUT)), 11387);
     }
+
+    #[test]
+    fn test_witness() {
+        let equations = input_generator(EXAMPLE_INPUT);
+        let ops: [&dyn Operator; 3] = [&Add, &Mul, &Concat];
+        let witnesses = equations
+            .iter()
+            .map(|equation| equation.witness(&ops))
+            .collect_vec();
+
+        assert_eq!(witnesses[0].as_deref(), Some("10 * 19"));
+        assert_eq!(witnesses[1].as_deref(), Some("81 * 40 + 27"));
+        assert_eq!(witnesses[2], None);
+        assert_eq!(witnesses[3].as_deref(), Some("15 || 6"));
+        assert_eq!(witnesses[4].as_deref(), Some("6 * 8 || 6 * 15"));
+    }
+
+    #[test]
+    fn test_operators_round_trip() {
+        let ops: [&dyn Operator; 8] = [&Add, &Mul, &Concat, &Sub, &Pow, &BitAnd, &BitOr, &BitXor];
+        for op in ops {
+            for left in 0..40 {
+                for right in 0..6 {
+                    let Some(result) = op.apply(left, right) else {
+                        continue;
+                    };
+                    if let Some(lefts) = op.undo(result, right) {
+                        assert!(lefts.contains(&left), "{} {left} {right}", op.symbol());
+                        assert!(lefts.iter().all(|&l| op.apply(l, right) == Some(result)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_extra_operators() {
+        let equation = |target, operands: &[u64]| Equation {
+            target,
+            operands: operands.to_vec(),
+        };
+        let ops: [&dyn Operator; 3] = [&Add, &Sub, &Pow];
+        assert_eq!(
+            equation(81, &[5, 2, 4]).witness(&ops).as_deref(),
+            Some("5 - 2 ^ 4")
+        );
+        assert_eq!(
+            equation(3, &[7, 0, 3]).witness(&[&Mul, &Add]).as_deref(),
+            Some("7 * 0 + 3")
+        );
+        assert_eq!(
+            equation(6, &[12, 10, 2])
+                .witness(&[&BitAnd, &BitOr, &BitXor])
+                .as_deref(),
+            Some("12 xor 10 | 2")
+        );
+        assert_eq!(equation(7, &[1, 2]).witness(&[&Add, &Mul]), None);
+    }
+
+    #[test]
+    fn test_undo_edge_cases() {
+        let big = (1 << 60) + 12345;
+        assert_eq!(Pow.undo(big, 1), Some(vec![big]));
+        assert_eq!(Pow.undo(u64::MAX, 1), Some(vec![u64::MAX]));
+        assert_eq!(Pow.undo(u64::MAX, 2), Some(vec![]));
+        assert_eq!(Pow.undo(u64::MAX, 64), Some(vec![]));
+        assert_eq!(Pow.undo(1 << 62, 2), Some(vec![1 << 31]));
+        assert_eq!(Pow.undo(4294967295 * 4294967295, 2), Some(vec![4294967295]));
+        assert_eq!(
+            Equation {
+                target: big,
+                operands: vec![big, 1],
+            }
+            .witness(&[&Pow])
+            .as_deref(),
+            Some("1152921504606859321 ^ 1")
+        );
+
+        assert_eq!(BitOr.undo(u64::MAX, (1 << 26) - 1), None);
+        assert_eq!(BitOr.undo(0b111, 0b011).map(|lefts| lefts.len()), Some(4));
+        assert_eq!(
+            Equation {
+                target: (1 << 26) - 1,
+                operands: vec![1 << 25, (1 << 26) - 1],
+            }
+            .witness(&[&BitOr])
+            .as_deref(),
+            Some("33554432 | 67108863")
+        );
+    }
 }
@@ -1,15 +1,100 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use itertools::Itertools;
 
-struct Input {
-    antennas: HashMap<char, Vec<[usize; 2]>>,
+use crate::{diophantine::extended_gcd, utils::Pos};
+
+pub struct AntennaMap {
+    antennas: HashMap<char, Vec<Pos>>,
     width: usize,
     height: usize,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Antinodes {
+    // One antinode beyond each antenna of a pair, at the pair's distance.
+    Pair,
+    // Every grid point on the line through a pair.
+    Harmonics,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bounds {
+    Grid,
+    // Also keeps antinodes up to this many cells outside the grid.
+    Margin(i64),
+}
+
+impl AntennaMap {
+    fn contains(&self, pos: Pos, bounds: Bounds) -> bool {
+        let margin = match bounds {
+            Bounds::Grid => 0,
+            Bounds::Margin(margin) => margin,
+        };
+        (-margin..self.width as i64 + margin).contains(&pos.x)
+            && (-margin..self.height as i64 + margin).contains(&pos.y)
+    }
+
+    // Grid points from `start` (exclusive) in steps of `step` while they stay within bounds.
+    fn ray(&self, start: Pos, step: Pos, bounds: Bounds) -> impl Iterator<Item = Pos> + '_ {
+        (1..)
+            .map(move |i| start + step * i)
+            .take_while(move |&pos| self.contains(pos, bounds))
+    }
+
+    fn pair_antinodes(&self, a: Pos, b: Pos, mode: Antinodes, bounds: Bounds) -> Vec<Pos> {
+        let offset = Pos {
+            x: b.x - a.x,
+            y: b.y - a.y,
+        };
+
+        match mode {
+            Antinodes::Pair => [a + offset * -1, b + offset]
+                .into_iter()
+                .filter(|&pos| self.contains(pos, bounds))
+                .collect(),
+            Antinodes::Harmonics => {
+                let (gcd, _, _) = extended_gcd(offset.x as i128, offset.y as i128);
+                let step = Pos {
+                    x: offset.x / gcd as i64,
+                    y: offset.y / gcd as i64,
+                };
+                self.ray(a, step * -1, bounds)
+                    .chain(std::iter::once(a).filter(|&a| self.contains(a, bounds)))
+                    .chain(self.ray(a, step, bounds))
+                    .collect()
+            }
+        }
+    }
+
+    pub fn antinodes_by_frequency(
+        &self,
+        mode: Antinodes,
+        bounds: Bounds,
+    ) -> HashMap<char, BTreeSet<Pos>> {
+        self.antennas
+            .iter()
+            .map(|(&frequency, positions)| {
+                let antinodes = positions
+                    .iter()
+                    .tuple_combinations()
+                    .flat_map(|(&a, &b)| self.pair_antinodes(a, b, mode, bounds))
+                    .collect();
+                (frequency, antinodes)
+            })
+            .collect()
+    }
+
+    pub fn antinodes(&self, mode: Antinodes, bounds: Bounds) -> BTreeSet<Pos> {
+        self.antinodes_by_frequency(mode, bounds)
+            .into_values()
+            .flatten()
+            .collect()
+    }
+}
+
 #[aoc_generator(day8)]
-fn input_generator(input: &str) -> Input {
+fn input_generator(input: &str) -> AntennaMap {
     let mut antennas = HashMap::new();
     let mut width = 0;
     let mut height = 0;
@@ -21,12 +106,15 @@ fn input_generator(input: &str) -> Input {
             '.' => {}
             _ => {
                 let entry = antennas.entry(c).or_insert(vec![]);
-                entry.push([x, y]);
+                entry.push(Pos {
+                    x: x as i64,
+                    y: y as i64,
+                });
             }
         });
     });
 
-    Input {
+    AntennaMap {
         antennas,
         width,
         height,
@@ -34,86 +122,13 @@ fn input_generator(input: &str) -> Input {
 }
 
 #[aoc(day8, part1)]
-fn part1(input: &Input) -> usize {
-    input
-        .antennas
-        .values()
-        .flat_map(|antenna_positions| {
-            antenna_positions.iter().enumerate().flat_map(|(i, ant1)| {
-                antenna_positions
-                    .iter()
-                    .skip(i + 1)
-                    .flat_map(|ant2| get_antinodes(ant1, ant2, input.width, input.height, false))
-            })
-        })
-        .sorted()
-        .dedup()
-        .count()
+fn part1(input: &AntennaMap) -> usize {
+    input.antinodes(Antinodes::Pair, Bounds::Grid).len()
 }
 
 #[aoc(day8, part2)]
-fn part2(input: &Input) -> usize {
-    input
-        .antennas
-        .values()
-        .filter(|antenna_positions| antenna_positions.len() > 1)
-        .flat_map(|antenna_positions| {
-            antenna_positions
-                .iter()
-                .enumerate()
-                .flat_map(|(i, ant1)| {
-                    antenna_positions
-                        .iter()
-                        .skip(i + 1)
-                        .flat_map(|ant2| get_antinodes(ant1, ant2, input.width, input.height, true))
-                })
-                .chain(
-                    antenna_positions
-                        .iter()
-                        .map(|&[x, y]| [x as isize, y as isize]),
-                )
-        })
-        .sorted()
-        .dedup()
-        .count()
-}
-
-fn get_antinodes(
-    ant1: &[usize; 2],
-    ant2: &[usize; 2],
-    width: usize,
-    height: usize,
-    with_harmonics: bool,
-) -> Vec<[isize; 2]> {
-    let x1 = ant1[0] as isize;
-    let y1 = ant1[1] as isize;
-    let x2 = ant2[0] as isize;
-    let y2 = ant2[1] as isize;
-
-    let offset_x = x2 - x1;
-    let offset_y = y2 - y1;
-
-    let res_iter_1 = anti_node_iter(x1, y1, -offset_x, -offset_y, width, height);
-    let res_iter_2 = anti_node_iter(x2, y2, offset_x, offset_y, width, height);
-
-    if !with_harmonics {
-        res_iter_1.take(1).chain(res_iter_2.take(1)).collect()
-    } else {
-        res_iter_1.chain(res_iter_2).collect()
-    }
-}
-
-fn anti_node_iter<'a>(
-    x0: isize,
-    y0: isize,
-    offset_x: isize,
-    offset_y: isize,
-    width: usize,
-    height: usize,
-) -> impl Iterator<Item = [isize; 2]> + 'a {
-    (1..)
-        .map(move |i| [x0 + i * offset_x, y0 + i * offset_y])
-        .take_while(move |&[x, y]| x >= 0 && y >= 0 && x < width as isize && y < height as isize)
+fn part2(input: &AntennaMap) -> usize {
+    input.antinodes(Antinodes::Harmonics, Bounds::Grid).len()
 }
 
 #[cfg(test)]
@@ -139,17 +154,59 @@ mod tests {
 
     #[test]
     fn iter_generator() {
+        let map = input_generator("...\n...\n...");
         assert_eq!(
-            anti_node_iter(0, 0, 1, 1, 3, 3).collect_vec(),
-            vec![[1, 1], [2, 2]]
+            map.ray(Pos { x: 0, y: 0 }, Pos { x: 1, y: 1 }, Bounds::Grid)
+                .collect_vec(),
+            vec![Pos { x: 1, y: 1 }, Pos { x: 2, y: 2 }]
         )
     }
 
     #[test]
     fn iter_generator_rev() {
+        let map = input_generator("...\n...\n...");
         assert_eq!(
-            anti_node_iter(2, 2, -1, -1, 3, 3).collect_vec(),
-            vec![[1, 1], [0, 0]]
+            map.ray(Pos { x: 2, y: 2 }, Pos { x: -1, y: -1 }, Bounds::Grid)
+                .collect_vec(),
+            vec![Pos { x: 1, y: 1 }, Pos { x: 0, y: 0 }]
         )
     }
+
+    #[test]
+    fn harmonics_step_by_reduced_offset() {
+        let map = input_generator("0.....\n......\n..0...\n......\n......\n......");
+        assert_eq!(
+            map.antinodes(Antinodes::Harmonics, Bounds::Grid),
+            (0..6).map(|i| Pos { x: i, y: i }).collect()
+        );
+        assert_eq!(
+            map.antinodes(Antinodes::Pair, Bounds::Grid),
+            BTreeSet::from([Pos { x: 4, y: 4 }])
+        );
+    }
+
+    #[test]
+    fn antinodes_outside_grid() {
+        let map = input_generator("....\n.0..\n..0.\n....");
+        assert_eq!(
+            map.antinodes(Antinodes::Pair, Bounds::Margin(4)),
+            BTreeSet::from([Pos { x: 0, y: 0 }, Pos { x: 3, y: 3 }])
+        );
+
+        let map = input_generator("0a..\n..a0");
+        assert_eq!(
+            map.antinodes(Antinodes::Pair, Bounds::Margin(3)),
+            BTreeSet::from([
+                Pos { x: -3, y: -1 },
+                Pos { x: 0, y: -1 },
+                Pos { x: 3, y: 2 },
+                Pos { x: 6, y: 2 },
+            ])
+        );
+        assert_eq!(map.antinodes(Antinodes::Pair, Bounds::Grid).len(), 0);
+
+        let by_frequency = map.antinodes_by_frequency(Antinodes::Harmonics, Bounds::Margin(1));
+        assert_eq!(by_frequency[&'a'].len(), 4);
+        assert_eq!(by_frequency[&'0'].len(), 2);
+    }
 }
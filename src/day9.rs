@@ -1,145 +1,424 @@
-use itertools::Itertools;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, BinaryHeap},
+};
 
-#[derive(Clone, Copy)]
-struct BlockSegment {
-    id: Option<usize>,
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Extent {
+    start: usize,
     length: usize,
 }
 
-impl PartialEq for BlockSegment {
-    fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+impl Extent {
+    fn end(&self) -> usize {
+        self.start + self.length
     }
 }
 
 #[derive(Clone)]
-struct BlockSegmentIterator<'a>(&'a BlockSegment, usize);
+pub struct Disk {
+    files: Vec<Vec<Extent>>,
+    size: usize,
+}
 
-impl<'a> Iterator for BlockSegmentIterator<'a> {
-    type Item = Option<usize>;
+pub trait Strategy {
+    fn apply(&self, disk: &mut Disk);
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.1 == self.0.length {
-            None
-        } else {
-            self.1 += 1;
-            Some(self.0.id)
+impl Disk {
+    pub fn apply(&mut self, strategy: &impl Strategy) {
+        strategy.apply(self);
+    }
+
+    // All file extents ordered by their position on the disk.
+    fn extents(&self) -> Vec<(usize, Extent)> {
+        let mut extents = self
+            .files
+            .iter()
+            .enumerate()
+            .flat_map(|(id, extents)| extents.iter().map(move |&extent| (id, extent)))
+            .collect::<Vec<_>>();
+        extents.sort_unstable_by_key(|(_, extent)| extent.start);
+        extents
+    }
+
+    fn gaps(&self) -> Vec<Extent> {
+        let mut gaps = Vec::new();
+        let mut pos = 0;
+
+        for (_, extent) in self.extents() {
+            if extent.start > pos {
+                gaps.push(Extent {
+                    start: pos,
+                    length: extent.start - pos,
+                });
+            }
+            pos = extent.end();
+        }
+
+        if self.size > pos {
+            gaps.push(Extent {
+                start: pos,
+                length: self.size - pos,
+            });
         }
+
+        gaps
     }
-}
 
-impl<'a> DoubleEndedIterator for BlockSegmentIterator<'a> {
-    fn next_back(&mut self) -> Option<Self::Item> {
-        self.next()
+    pub fn checksum(&self) -> usize {
+        self.files
+            .iter()
+            .enumerate()
+            .flat_map(|(id, extents)| {
+                extents.iter().map(move |extent| {
+                    id * (extent.start * extent.length + extent.length * (extent.length - 1) / 2)
+                })
+            })
+            .sum()
+    }
+
+    // File ids are rendered modulo 10, so the layout is only unambiguous for up to 10 files.
+    pub fn layout(&self) -> String {
+        let mut layout = vec!['.'; self.size];
+
+        for (id, extent) in self.extents() {
+            let c = char::from_digit((id % 10) as u32, 10).unwrap();
+            layout[extent.start..extent.end()].fill(c);
+        }
+
+        layout.into_iter().collect()
     }
 }
 
-impl<'a> IntoIterator for &'a BlockSegment {
-    type Item = Option<usize>;
-    type IntoIter = BlockSegmentIterator<'a>;
+impl From<&str> for Disk {
+    fn from(input: &str) -> Self {
+        let mut files = Vec::new();
+        let mut pos = 0;
 
-    fn into_iter(self) -> Self::IntoIter {
-        BlockSegmentIterator(self, 0)
+        for (i, length) in input
+            .lines()
+            .flat_map(|line| line.chars())
+            .map(|c| c.to_digit(10).unwrap() as usize)
+            .enumerate()
+        {
+            if i % 2 == 0 {
+                files.push(
+                    (length > 0)
+                        .then_some(Extent { start: pos, length })
+                        .into_iter()
+                        .collect(),
+                );
+            }
+            pos += length;
+        }
+
+        Disk { files, size: pos }
     }
 }
 
-type DiskMap = Vec<BlockSegment>;
+// Files are at most 9 blocks long, so gaps shorter than that are kept in exact min-heaps of their
+// starts while longer gaps share one overflow index.
+const OVERFLOW: usize = 9;
 
-#[aoc_generator(day9)]
-fn input_generator(input: &str) -> DiskMap {
-    input
-        .lines()
-        .flat_map(|line| line.chars())
-        .map(|c| c as i32 - 0x30)
-        .enumerate()
-        .map(|(i, segment_size)| BlockSegment {
-            id: if i % 2 == 0 { Some(i / 2) } else { None },
-            length: segment_size as usize,
-        })
-        .collect()
+// Free space indexed by position in `gaps`, which is authoritative. The size indexes only cover
+// gaps starting before `limit`, the smallest `before` queried so far. Files are moved from the end
+// of the disk towards the start, so queries rarely raise it again, and everything past it can be
+// dropped as soon as it is seen. Heap entries are removed lazily, an entry is only valid while its
+// start still holds a gap of a matching length in `gaps`.
+struct FreeSpace {
+    gaps: BTreeMap<usize, usize>,
+    exact: Vec<BinaryHeap<Reverse<usize>>>,
+    overflow_by_start: BinaryHeap<Reverse<usize>>,
+    overflow_by_size: BTreeSet<(usize, usize)>,
+    limit: usize,
 }
 
-#[aoc(day9, part1)]
-fn part1(input: &DiskMap) -> usize {
-    let simplified_input = input
-        .iter()
-        .flat_map(|block_segment| block_segment.into_iter());
-
-    let mut rev_iter = simplified_input.clone().flatten().rev();
-
-    simplified_input
-        .clone()
-        .take(simplified_input.clone().flatten().count())
-        .map(|block_segment| match block_segment {
-            Some(id) => id,
-            None => rev_iter.next().unwrap(),
+impl FreeSpace {
+    fn new(gaps: Vec<Extent>) -> Self {
+        let mut free_space = FreeSpace {
+            gaps: BTreeMap::new(),
+            exact: vec![BinaryHeap::new(); OVERFLOW],
+            overflow_by_start: BinaryHeap::new(),
+            overflow_by_size: BTreeSet::new(),
+            limit: usize::MAX,
+        };
+        for gap in gaps {
+            free_space.insert(gap);
+        }
+        free_space
+    }
+
+    fn insert(&mut self, gap: Extent) {
+        if gap.length == 0 {
+            return;
+        }
+        self.gaps.insert(gap.start, gap.length);
+        self.index(gap);
+    }
+
+    fn index(&mut self, gap: Extent) {
+        if gap.start >= self.limit {
+            return;
+        }
+        if gap.length < OVERFLOW {
+            self.exact[gap.length].push(Reverse(gap.start));
+        } else {
+            self.overflow_by_start.push(Reverse(gap.start));
+            self.overflow_by_size.insert((gap.length, gap.start));
+        }
+    }
+
+    fn remove(&mut self, start: usize) -> Option<usize> {
+        let length = self.gaps.remove(&start)?;
+        if length >= OVERFLOW {
+            self.overflow_by_size.remove(&(length, start));
+        }
+        Some(length)
+    }
+
+    // Lowers the limit to `before`, or rebuilds the size indexes if a query goes past it.
+    fn restrict(&mut self, before: usize) {
+        if before <= self.limit {
+            self.limit = before;
+            return;
+        }
+        self.limit = before;
+        self.exact.iter_mut().for_each(BinaryHeap::clear);
+        self.overflow_by_start.clear();
+        self.overflow_by_size.clear();
+        let gaps = self
+            .gaps
+            .range(..before)
+            .map(|(&start, &length)| Extent { start, length });
+        for gap in gaps.collect::<Vec<_>>() {
+            self.index(gap);
+        }
+    }
+
+    fn leftmost(
+        heap: &mut BinaryHeap<Reverse<usize>>,
+        before: usize,
+        valid: impl Fn(usize) -> bool,
+    ) -> Option<usize> {
+        while let Some(&Reverse(start)) = heap.peek() {
+            if start < before && valid(start) {
+                return Some(start);
+            }
+            heap.pop();
+        }
+        None
+    }
+
+    fn leftmost_exact(&mut self, length: usize, before: usize) -> Option<Extent> {
+        let gaps = &self.gaps;
+        let start = Self::leftmost(&mut self.exact[length], before, |start| {
+            gaps.get(&start) == Some(&length)
+        })?;
+        Some(Extent { start, length })
+    }
+
+    fn leftmost_overflow(&mut self, length: usize, before: usize) -> Option<Extent> {
+        if length > OVERFLOW {
+            // Only reachable for files that could not come from a disk map.
+            return self
+                .gaps
+                .range(..before)
+                .find(|&(_, &gap)| gap >= length)
+                .map(|(&start, &length)| Extent { start, length });
+        }
+        let gaps = &self.gaps;
+        let start = Self::leftmost(&mut self.overflow_by_start, before, |start| {
+            gaps.get(&start).is_some_and(|&length| length >= OVERFLOW)
+        })?;
+        Some(Extent {
+            start,
+            length: gaps[&start],
         })
-        .enumerate()
-        .map(|(i, id)| i * id)
-        .sum()
+    }
+
+    fn smallest_overflow(&mut self, length: usize, before: usize) -> Option<Extent> {
+        let from = (length.max(OVERFLOW), 0);
+        while let Some(&(size, start)) = self.overflow_by_size.range(from..).next() {
+            if start < before {
+                return Some(Extent {
+                    start,
+                    length: size,
+                });
+            }
+            // Past the limit, so no later query can use it until the indexes are rebuilt.
+            self.overflow_by_size.remove(&(size, start));
+        }
+        None
+    }
+
+    fn allocate(&mut self, gap: Extent, length: usize) -> usize {
+        self.remove(gap.start);
+        self.insert(Extent {
+            start: gap.start + length,
+            length: gap.length - length,
+        });
+        gap.start
+    }
+
+    fn first_fit(&mut self, length: usize, before: usize) -> Option<usize> {
+        self.restrict(before);
+        let overflow = self.leftmost_overflow(length, before);
+        let gap = (length.max(1)..OVERFLOW)
+            .filter_map(|size| self.leftmost_exact(size, before))
+            .chain(overflow)
+            .min_by_key(|gap| gap.start)?;
+        Some(self.allocate(gap, length))
+    }
+
+    fn best_fit(&mut self, length: usize, before: usize) -> Option<usize> {
+        self.restrict(before);
+        let gap = (length.max(1)..OVERFLOW)
+            .find_map(|size| self.leftmost_exact(size, before))
+            .or_else(|| self.smallest_overflow(length, before))?;
+        Some(self.allocate(gap, length))
+    }
+
+    fn release(&mut self, extent: Extent) {
+        let mut merged = extent;
+
+        if let Some((&start, &length)) = self.gaps.range(..extent.start).next_back() {
+            if start + length == extent.start {
+                self.remove(start);
+                merged.start = start;
+                merged.length += length;
+            }
+        }
+        if let Some(length) = self.remove(extent.end()) {
+            merged.length += length;
+        }
+
+        self.insert(merged);
+    }
 }
 
-#[aoc(day9, part2)]
-fn part2(input: &DiskMap) -> usize {
-    let mut ids_to_skip: Vec<usize> = vec![];
-
-    input
-        .iter()
-        .enumerate()
-        .flat_map(|(i, block_segment)| match block_segment.id {
-            Some(id) => {
-                if ids_to_skip.contains(&id) {
-                    vec![BlockSegment {
-                        id: None,
-                        length: block_segment.length,
-                    }]
-                } else {
-                    vec![*block_segment]
+// Moves single blocks from the end of the disk into the leftmost free block.
+pub struct BlockCompaction;
+
+impl Strategy for BlockCompaction {
+    fn apply(&self, disk: &mut Disk) {
+        let mut gaps = disk.gaps().into_iter().peekable();
+        let mut extents = disk.extents();
+        let mut files = vec![Vec::new(); disk.files.len()];
+
+        while let Some((id, extent)) = extents.pop() {
+            let mut remaining = extent.length;
+
+            while remaining > 0 {
+                let Some(gap) = gaps.peek_mut() else {
+                    break;
+                };
+                if gap.start >= extent.start {
+                    break;
+                }
+
+                let moved = gap.length.min(remaining);
+                files[id].push(Extent {
+                    start: gap.start,
+                    length: moved,
+                });
+                gap.start += moved;
+                gap.length -= moved;
+                remaining -= moved;
+
+                if gap.length == 0 {
+                    gaps.next();
                 }
             }
-            None => {
-                let mut length_remaining = block_segment.length;
-                let mut blocks = vec![];
-
-                input
-                    .iter()
-                    .skip(i + 1)
-                    .rev()
-                    .skip_while(|block_segment| block_segment.id.is_none())
-                    .step_by(2)
-                    .for_each(|block_segment| {
-                        if length_remaining < block_segment.length
-                            || ids_to_skip.contains(&block_segment.id.unwrap())
-                        {
-                            return;
-                        }
-
-                        length_remaining -= block_segment.length;
-
-                        blocks.push(*block_segment);
-                        ids_to_skip.push(block_segment.id.unwrap());
-                    });
-
-                blocks.push(BlockSegment {
-                    id: None,
-                    length: length_remaining,
+
+            if remaining > 0 {
+                files[id].push(Extent {
+                    start: extent.start,
+                    length: remaining,
                 });
+            }
+        }
+
+        for extents in files.iter_mut() {
+            extents.sort_unstable_by_key(|extent| extent.start);
+        }
+        disk.files = files;
+    }
+}
 
-                blocks
+fn move_whole_files(disk: &mut Disk, find: impl Fn(&mut FreeSpace, usize, usize) -> Option<usize>) {
+    let mut free_space = FreeSpace::new(disk.gaps());
+
+    for extents in disk.files.iter_mut().rev() {
+        for extent in extents.iter_mut() {
+            if let Some(start) = find(&mut free_space, extent.length, extent.start) {
+                free_space.release(*extent);
+                extent.start = start;
             }
-        })
-        .flat_map(|block_segment| block_segment.into_iter().collect_vec())
-        .map(|block_segment| match block_segment {
-            Some(id) => id,
-            None => 0,
-        })
-        .enumerate()
-        .map(|(i, id)| i * id)
-        .sum()
+        }
+        extents.sort_unstable_by_key(|extent| extent.start);
+    }
+}
+
+// Moves each file, highest id first, into the leftmost gap that fits it entirely.
+pub struct FirstFit;
+
+impl Strategy for FirstFit {
+    fn apply(&self, disk: &mut Disk) {
+        move_whole_files(disk, FreeSpace::first_fit);
+    }
+}
+
+// Moves each file, highest id first, into the smallest gap to its left that fits it entirely.
+pub struct BestFit;
+
+impl Strategy for BestFit {
+    fn apply(&self, disk: &mut Disk) {
+        move_whole_files(disk, FreeSpace::best_fit);
+    }
+}
+
+// Lays out every file contiguously in id order with no free space in between.
+pub struct Defragmentation;
+
+impl Strategy for Defragmentation {
+    fn apply(&self, disk: &mut Disk) {
+        let mut pos = 0;
+
+        for extents in disk.files.iter_mut() {
+            let length = extents.iter().map(|extent| extent.length).sum();
+            *extents = (length > 0)
+                .then_some(Extent { start: pos, length })
+                .into_iter()
+                .collect();
+            pos += length;
+        }
+    }
+}
+
+#[aoc_generator(day9)]
+fn input_generator(input: &str) -> Disk {
+    Disk::from(input)
+}
+
+#[aoc(day9, part1)]
+fn part1(input: &Disk) -> usize {
+    let mut disk = input.clone();
+    disk.apply(&BlockCompaction);
+    disk.checksum()
+}
+
+#[aoc(day9, part2)]
+fn part2(input: &Disk) -> usize {
+    let mut disk = input.clone();
+    disk.apply(&FirstFit);
+    disk.checksum()
 }
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use super::*;
 
     const EXAMPLE_INPUT: &str = "2333133121414131402";
@@ -153,4 +432,133 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&input_generator(EXAMPLE_INPUT)), 2858);
     }
+
+    #[test]
+    fn test_layouts() {
+        let disk = input_generator(EXAMPLE_INPUT);
+        assert_eq!(disk.layout(), "00...111...2...333.44.5555.6666.777.888899");
+
+        let mut compacted = disk.clone();
+        compacted.apply(&BlockCompaction);
+        assert_eq!(
+            compacted.layout(),
+            "0099811188827773336446555566.............."
+        );
+
+        let mut first_fit = disk.clone();
+        first_fit.apply(&FirstFit);
+        assert_eq!(
+            first_fit.layout(),
+            "00992111777.44.333....5555.6666.....8888.."
+        );
+
+        let mut defragmented = compacted.clone();
+        defragmented.apply(&Defragmentation);
+        assert_eq!(
+            defragmented.layout(),
+            "0011123334455556666777888899.............."
+        );
+        assert_eq!(defragmented.checksum(), 2453);
+    }
+
+    #[test]
+    fn test_best_fit() {
+        let mut disk = input_generator("15122");
+        disk.apply(&FirstFit);
+        assert_eq!(disk.layout(), "0221.......");
+
+        let mut disk = input_generator("15122");
+        disk.apply(&BestFit);
+        assert_eq!(disk.layout(), "01.....22..");
+
+        let gaps = |lengths: [(usize, usize); 2]| {
+            FreeSpace::new(
+                lengths
+                    .into_iter()
+                    .map(|(start, length)| Extent { start, length })
+                    .collect(),
+            )
+        };
+        assert_eq!(gaps([(0, 12), (20, 10)]).best_fit(9, 100), Some(20));
+        assert_eq!(gaps([(0, 9), (20, 15)]).first_fit(11, 100), Some(20));
+        assert_eq!(gaps([(0, 9), (20, 15)]).first_fit(11, 20), None);
+    }
+
+    // Moves files by scanning the block list for maximal free runs.
+    fn naive_move(input: &str, best: bool) -> usize {
+        let disk = input_generator(input);
+        let mut blocks = vec![None; disk.size];
+        for (id, extent) in disk.extents() {
+            blocks[extent.start..extent.end()].fill(Some(id));
+        }
+
+        for id in (0..disk.files.len()).rev() {
+            let Some(start) = blocks.iter().position(|&b| b == Some(id)) else {
+                continue;
+            };
+            let length = blocks[start..]
+                .iter()
+                .take_while(|&&b| b == Some(id))
+                .count();
+            let runs = blocks[..start]
+                .iter()
+                .enumerate()
+                .chunk_by(|(_, b)| b.is_none())
+                .into_iter()
+                .filter(|(free, _)| *free)
+                .map(|(_, run)| {
+                    let run = run.collect_vec();
+                    (run.len(), run[0].0)
+                })
+                .filter(|&(run_length, _)| run_length >= length)
+                .collect_vec();
+            let target = if best {
+                runs.into_iter().min()
+            } else {
+                runs.into_iter().min_by_key(|&(_, target)| target)
+            };
+            if let Some((_, target)) = target {
+                blocks[start..start + length].fill(None);
+                blocks[target..target + length].fill(Some(id));
+            }
+        }
+
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(i, id)| i * id.unwrap_or(0))
+            .sum()
+    }
+
+    fn generated_input(digits: u64) -> String {
+        (0..digits)
+            .map(|i| {
+                char::from_digit(((i * 7919 + i / 3) % 9 + (i % 2 == 0) as u64) as u32, 10).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fits_match_naive() {
+        let input = generated_input(2_000);
+        assert_eq!(part2(&input_generator(&input)), naive_move(&input, false));
+
+        let mut disk = input_generator(&input);
+        disk.apply(&BestFit);
+        assert_eq!(disk.checksum(), naive_move(&input, true));
+    }
+
+    #[test]
+    fn test_large_disk() {
+        let disk = input_generator(&generated_input(1_000_000));
+        let started = std::time::Instant::now();
+        let mut first_fit = disk.clone();
+        first_fit.apply(&FirstFit);
+        let mut best_fit = disk.clone();
+        best_fit.apply(&BestFit);
+        // Scanning every gap length per file took minutes here.
+        assert!(started.elapsed() < std::time::Duration::from_secs(20));
+        assert!(first_fit.checksum() < disk.checksum());
+        assert!(best_fit.checksum() < disk.checksum());
+    }
 }
@@ -0,0 +1,133 @@
+// Exact solvers for small linear Diophantine problems over non-negative integers.
+
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        if a < 0 {
+            (-a, -1, 0)
+        } else {
+            (a, 1, 0)
+        }
+    } else {
+        let (g, x, y) = extended_gcd(b, a.rem_euclid(b));
+        (g, y, x - a.div_euclid(b) * y)
+    }
+}
+
+fn floor_div(a: i128, b: i128) -> i128 {
+    if b < 0 {
+        (-a).div_euclid(-b)
+    } else {
+        a.div_euclid(b)
+    }
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    -floor_div(-a, b)
+}
+
+// Cheapest non-negative (x, y) with `u * x + v * y == w`, for non-negative costs.
+pub fn cheapest_solution_1d(u: i128, v: i128, w: i128, costs: [i128; 2]) -> Option<(i128, i128)> {
+    let (g, x0, y0) = extended_gcd(u, v);
+    if g == 0 {
+        return (w == 0).then_some((0, 0));
+    }
+    if w % g != 0 {
+        return None;
+    }
+
+    // All solutions are x = x0 + k * p, y = y0 - k * q.
+    let (x0, y0) = (x0 * (w / g), y0 * (w / g));
+    let (p, q) = (v / g, u / g);
+
+    let mut low = None::<i128>;
+    let mut high = None::<i128>;
+    for (base, step) in [(x0, p), (y0, -q)] {
+        match step.signum() {
+            1 => low = Some(low.unwrap_or(i128::MIN).max(ceil_div(-base, step))),
+            -1 => high = Some(high.unwrap_or(i128::MAX).min(floor_div(base, -step))),
+            _ if base < 0 => return None,
+            _ => {}
+        }
+    }
+    if let (Some(l), Some(h)) = (low, high) {
+        if l > h {
+            return None;
+        }
+    }
+
+    let slope = costs[0] * p - costs[1] * q;
+    let k = match (slope.signum(), low, high) {
+        (1 | 0, Some(l), _) => l,
+        (-1 | 0, _, Some(h)) => h,
+        _ => return None,
+    };
+
+    Some((x0 + k * p, y0 - k * q))
+}
+
+// Cheapest non-negative (x, y) with `x * a + y * b == target`, where a, b and target are
+// 2D vectors. Handles both independent and collinear a and b.
+pub fn cheapest_solution_2d(
+    a: [i128; 2],
+    b: [i128; 2],
+    target: [i128; 2],
+    costs: [i128; 2],
+) -> Option<(i128, i128)> {
+    let det = a[0] * b[1] - a[1] * b[0];
+
+    if det != 0 {
+        let x_numerator = target[0] * b[1] - target[1] * b[0];
+        let y_numerator = a[0] * target[1] - a[1] * target[0];
+        if x_numerator % det != 0 || y_numerator % det != 0 {
+            return None;
+        }
+        let (x, y) = (x_numerator / det, y_numerator / det);
+        return (x >= 0 && y >= 0).then_some((x, y));
+    }
+
+    // With a singular matrix the system is only consistent if both rows are proportional,
+    // in which case it reduces to whichever row is not identically zero.
+    if a[0] * target[1] != a[1] * target[0] || b[0] * target[1] != b[1] * target[0] {
+        return None;
+    }
+    let row = if a[0] != 0 || b[0] != 0 { 0 } else { 1 };
+    let (x, y) = cheapest_solution_1d(a[row], b[row], target[row], costs)?;
+
+    (x * a[1 - row] + y * b[1 - row] == target[1 - row]).then_some((x, y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extended_gcd() {
+        for (a, b) in [(240, 46), (-240, 46), (0, 7), (7, 0), (17, -5)] {
+            let (g, x, y) = extended_gcd(a, b);
+            assert_eq!(a * x + b * y, g);
+            assert!(g >= 0);
+        }
+        assert_eq!(extended_gcd(240, 46).0, 2);
+    }
+
+    #[test]
+    fn test_1d() {
+        assert_eq!(cheapest_solution_1d(2, 1, 10, [3, 1]), Some((0, 10)));
+        assert_eq!(cheapest_solution_1d(4, 1, 10, [3, 1]), Some((2, 2)));
+        assert_eq!(cheapest_solution_1d(4, 6, 7, [3, 1]), None);
+        assert_eq!(cheapest_solution_1d(0, 0, 0, [3, 1]), Some((0, 0)));
+    }
+
+    #[test]
+    fn test_2d_collinear() {
+        assert_eq!(
+            cheapest_solution_2d([4, 8], [1, 2], [10, 20], [3, 1]),
+            Some((2, 2))
+        );
+        assert_eq!(cheapest_solution_2d([4, 8], [1, 2], [10, 21], [3, 1]), None);
+        assert_eq!(
+            cheapest_solution_2d([0, 3], [0, 5], [0, 11], [3, 1]),
+            Some((2, 1))
+        );
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use itertools::Itertools;
+
+pub struct Graph<'a> {
+    names: Vec<&'a str>,
+    ids: HashMap<&'a str, usize>,
+    adjacency: Vec<Vec<usize>>,
+}
+
+impl<'a> Graph<'a> {
+    fn intern(&mut self, name: &'a str) -> usize {
+        *self.ids.entry(name).or_insert_with(|| {
+            self.names.push(name);
+            self.adjacency.push(Vec::new());
+            self.names.len() - 1
+        })
+    }
+
+    pub fn name(&self, id: usize) -> &'a str {
+        self.names[id]
+    }
+
+    pub fn id(&self, name: &str) -> Option<usize> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.adjacency.iter().map(Vec::len).sum::<usize>() / 2
+    }
+
+    pub fn degree(&self, id: usize) -> usize {
+        self.adjacency[id].len()
+    }
+
+    pub fn degree_distribution(&self) -> BTreeMap<usize, usize> {
+        self.adjacency
+            .iter()
+            .map(Vec::len)
+            .counts()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn connected_components(&self) -> Vec<Vec<&'a str>> {
+        let mut visited = vec![false; self.names.len()];
+        let mut components = Vec::new();
+
+        for start in 0..self.names.len() {
+            if visited[start] {
+                continue;
+            }
+
+            visited[start] = true;
+            let mut stack = vec![start];
+            let mut component = Vec::new();
+
+            while let Some(id) = stack.pop() {
+                component.push(self.name(id));
+                for &next in self.neighbours(id) {
+                    if !visited[next] {
+                        visited[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+
+            component.sort_unstable();
+            components.push(component);
+        }
+
+        components
+    }
+
+    pub fn neighbours(&self, id: usize) -> &[usize] {
+        &self.adjacency[id]
+    }
+
+    pub fn has_edge(&self, a: usize, b: usize) -> bool {
+        self.adjacency[a].binary_search(&b).is_ok()
+    }
+
+    fn common_neighbours(&self, nodes: &[usize], id: usize) -> Vec<usize> {
+        nodes
+            .iter()
+            .copied()
+            .filter(|&node| self.has_edge(id, node))
+            .collect()
+    }
+
+    pub fn triangles(&self) -> Vec<[usize; 3]> {
+        let mut triangles = Vec::new();
+
+        for a in 0..self.names.len() {
+            for &b in self.neighbours(a).iter().filter(|&&b| b > a) {
+                for &c in self.neighbours(b).iter().filter(|&&c| c > b) {
+                    if self.has_edge(a, c) {
+                        triangles.push([a, b, c]);
+                    }
+                }
+            }
+        }
+
+        triangles
+    }
+
+    // Repeatedly removes a node of minimum remaining degree, which yields both the degeneracy
+    // ordering and the core number of every node.
+    fn peel(&self) -> (Vec<usize>, Vec<usize>) {
+        let mut degrees = self.adjacency.iter().map(Vec::len).collect_vec();
+        let mut queue = degrees
+            .iter()
+            .enumerate()
+            .map(|(id, &degree)| (degree, id))
+            .collect::<BTreeSet<_>>();
+        let mut order = Vec::with_capacity(self.names.len());
+        let mut cores = vec![0; self.names.len()];
+        let mut core = 0;
+
+        while let Some((degree, id)) = queue.pop_first() {
+            core = core.max(degree);
+            cores[id] = core;
+            order.push(id);
+            for &next in self.neighbours(id) {
+                if queue.remove(&(degrees[next], next)) {
+                    degrees[next] -= 1;
+                    queue.insert((degrees[next], next));
+                }
+            }
+        }
+
+        (order, cores)
+    }
+
+    pub fn degeneracy_order(&self) -> Vec<usize> {
+        self.peel().0
+    }
+
+    pub fn core_numbers(&self) -> Vec<usize> {
+        self.peel().1
+    }
+
+    pub fn k_core(&self, k: usize) -> Vec<&'a str> {
+        self.core_numbers()
+            .iter()
+            .enumerate()
+            .filter(|&(_, &core)| core >= k)
+            .map(|(id, _)| self.name(id))
+            .sorted()
+            .collect()
+    }
+
+    fn bron_kerbosch(
+        &self,
+        clique: &mut Vec<usize>,
+        mut candidates: Vec<usize>,
+        mut excluded: Vec<usize>,
+        report: &mut impl FnMut(&[usize]),
+    ) {
+        if candidates.is_empty() {
+            if excluded.is_empty() {
+                report(clique);
+            }
+            return;
+        }
+
+        let pivot = candidates
+            .iter()
+            .chain(excluded.iter())
+            .copied()
+            .max_by_key(|&u| candidates.iter().filter(|&&v| self.has_edge(u, v)).count())
+            .unwrap();
+
+        for v in candidates.clone() {
+            if self.has_edge(pivot, v) {
+                continue;
+            }
+
+            clique.push(v);
+            self.bron_kerbosch(
+                clique,
+                self.common_neighbours(&candidates, v),
+                self.common_neighbours(&excluded, v),
+                report,
+            );
+            clique.pop();
+
+            candidates.retain(|&u| u != v);
+            excluded.push(v);
+        }
+    }
+
+    pub fn for_each_maximal_clique(&self, mut report: impl FnMut(&[usize])) {
+        let order = self.degeneracy_order();
+        let mut rank = vec![0; order.len()];
+        for (i, &id) in order.iter().enumerate() {
+            rank[id] = i;
+        }
+
+        for &v in &order {
+            let (later, earlier): (Vec<usize>, Vec<usize>) =
+                self.neighbours(v).iter().partition(|&&u| rank[u] > rank[v]);
+            self.bron_kerbosch(&mut vec![v], later, earlier, &mut report);
+        }
+    }
+
+    pub fn maximal_cliques(&self) -> Vec<Vec<&'a str>> {
+        let mut cliques = Vec::new();
+        self.for_each_maximal_clique(|clique| {
+            cliques.push(
+                clique
+                    .iter()
+                    .map(|&id| self.name(id))
+                    .sorted()
+                    .collect_vec(),
+            );
+        });
+        cliques.sort_unstable();
+        cliques
+    }
+
+    pub fn clique_size_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        self.for_each_maximal_clique(|clique| *histogram.entry(clique.len()).or_insert(0) += 1);
+        histogram
+    }
+
+    pub fn maximum_clique(&self) -> Vec<&'a str> {
+        let mut best: Vec<&'a str> = Vec::new();
+
+        self.for_each_maximal_clique(|clique| {
+            let names = clique
+                .iter()
+                .map(|&id| self.name(id))
+                .sorted()
+                .collect_vec();
+            if names.len() > best.len() || (names.len() == best.len() && names < best) {
+                best = names;
+            }
+        });
+
+        best
+    }
+
+    pub fn to_dot(&self) -> String {
+        let id = |node: usize| dot_id(self.name(node));
+        let mut dot = String::from("graph {\n");
+        for (a, neighbours) in self.adjacency.iter().enumerate() {
+            if neighbours.is_empty() {
+                writeln!(dot, "    {};", id(a)).unwrap();
+            }
+            for &b in neighbours.iter().filter(|&&b| b > a) {
+                writeln!(dot, "    {} -- {};", id(a), id(b)).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// A quoted DOT identifier. Inside quotes only `"` needs escaping, but a trailing `\` would
+// escape the closing quote, so backslashes are escaped as well.
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl<'a> From<&'a str> for Graph<'a> {
+    fn from(input: &'a str) -> Self {
+        let mut graph = Graph {
+            names: Vec::new(),
+            ids: HashMap::new(),
+            adjacency: Vec::new(),
+        };
+
+        for line in input.lines() {
+            let (name1, name2) = line.split_once("-").unwrap();
+            let (node1, node2) = (graph.intern(name1), graph.intern(name2));
+            graph.adjacency[node1].push(node2);
+            graph.adjacency[node2].push(node1);
+        }
+
+        for neighbours in graph.adjacency.iter_mut() {
+            neighbours.sort_unstable();
+            neighbours.dedup();
+        }
+
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_INPUT: &str = "a-b
+b-c
+c-a
+c-d
+d-e
+x-y";
+
+    #[test]
+    fn test_components_and_degrees() {
+        let graph = Graph::from(EXAMPLE_INPUT);
+        assert_eq!(graph.node_count(), 7);
+        assert_eq!(graph.edge_count(), 6);
+        assert_eq!(
+            graph.connected_components(),
+            vec![vec!["a", "b", "c", "d", "e"], vec!["x", "y"]]
+        );
+        assert_eq!(
+            graph.degree_distribution(),
+            BTreeMap::from([(1, 3), (2, 3), (3, 1)])
+        );
+    }
+
+    #[test]
+    fn test_cores() {
+        let graph = Graph::from(EXAMPLE_INPUT);
+        assert_eq!(graph.k_core(2), vec!["a", "b", "c"]);
+        assert_eq!(graph.core_numbers()[graph.id("e").unwrap()], 1);
+    }
+
+    #[test]
+    fn test_cliques() {
+        let graph = Graph::from(EXAMPLE_INPUT);
+        assert_eq!(
+            graph.maximal_cliques(),
+            vec![
+                vec!["a", "b", "c"],
+                vec!["c", "d"],
+                vec!["d", "e"],
+                vec!["x", "y"]
+            ]
+        );
+        assert_eq!(
+            graph.clique_size_histogram(),
+            BTreeMap::from([(2, 3), (3, 1)])
+        );
+        assert_eq!(graph.maximum_clique(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let graph = Graph::from("a-b\nb-c");
+        assert_eq!(
+            graph.to_dot(),
+            "graph {\n    \"a\" -- \"b\";\n    \"b\" -- \"c\";\n}\n"
+        );
+
+        let graph = Graph::from("say \"hi\"-back\\slash");
+        assert_eq!(
+            graph.to_dot(),
+            "graph {\n    \"say \\\"hi\\\"\" -- \"back\\\\slash\";\n}\n"
+        );
+    }
+}
@@ -3,7 +3,14 @@ extern crate aoc_runner;
 #[macro_use]
 extern crate aoc_runner_derive;
 
+pub mod diophantine;
+pub mod graph;
+pub mod list_stats;
+pub mod search;
+#[cfg(test)]
+mod test_utils;
 pub mod utils;
+pub mod visualiser;
 
 pub mod day1;
 pub mod day2;
@@ -0,0 +1,263 @@
+// Comparison of two streams of numbers through their frequency tables, so memory grows with the
+// number of distinct values rather than the length of the streams.
+
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrequencyTable {
+    counts: BTreeMap<i64, u64>,
+    len: u64,
+}
+
+impl FrequencyTable {
+    pub fn insert(&mut self, value: i64) {
+        *self.counts.entry(value).or_default() += 1;
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn count(&self, value: i64) -> u64 {
+        self.counts.get(&value).copied().unwrap_or(0)
+    }
+
+    // Distinct values in ascending order with their counts.
+    pub fn iter(&self) -> impl Iterator<Item = (i64, u64)> + '_ {
+        self.counts.iter().map(|(&value, &count)| (value, count))
+    }
+
+    pub fn min(&self) -> Option<i64> {
+        self.counts.keys().next().copied()
+    }
+
+    pub fn max(&self) -> Option<i64> {
+        self.counts.keys().next_back().copied()
+    }
+
+    // Number of values strictly smaller than `value`.
+    pub fn rank(&self, value: i64) -> u64 {
+        self.counts.range(..value).map(|(_, &count)| count).sum()
+    }
+
+    // The value at position `rank` (0-based) of the sorted list.
+    pub fn nth(&self, rank: u64) -> Option<i64> {
+        let mut seen = 0;
+        self.iter().find_map(|(value, count)| {
+            seen += count;
+            (rank < seen).then_some(value)
+        })
+    }
+
+    // Lower median for lists of even length.
+    pub fn median(&self) -> Option<i64> {
+        self.nth(self.len.checked_sub(1)? / 2)
+    }
+
+    // Nearest-rank quantile for `q` in `0.0..=1.0`.
+    pub fn quantile(&self, q: f64) -> Option<i64> {
+        let rank = (q.clamp(0.0, 1.0) * self.len as f64).ceil() as u64;
+        self.nth(rank.saturating_sub(1))
+    }
+}
+
+impl<T: Into<i64>> FromIterator<T> for FrequencyTable {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut table = FrequencyTable::default();
+        table.extend(iter);
+        table
+    }
+}
+
+impl<T: Into<i64>> Extend<T> for FrequencyTable {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value.into());
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LengthMismatch {
+    pub left: u64,
+    pub right: u64,
+}
+
+impl fmt::Display for LengthMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "lists have different lengths ({} and {})",
+            self.left, self.right
+        )
+    }
+}
+
+impl std::error::Error for LengthMismatch {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimilarityOverflow;
+
+impl fmt::Display for SimilarityOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "similarity score does not fit in an i128")
+    }
+}
+
+impl std::error::Error for SimilarityOverflow {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListComparison {
+    pub left: FrequencyTable,
+    pub right: FrequencyTable,
+}
+
+impl ListComparison {
+    pub fn from_streams<L, R>(left: L, right: R) -> Self
+    where
+        L: IntoIterator,
+        L::Item: Into<i64>,
+        R: IntoIterator,
+        R::Item: Into<i64>,
+    {
+        ListComparison {
+            left: left.into_iter().collect(),
+            right: right.into_iter().collect(),
+        }
+    }
+
+    pub fn from_pairs<T: Into<i64>>(pairs: impl IntoIterator<Item = (T, T)>) -> Self {
+        let mut comparison = ListComparison::default();
+        for (left, right) in pairs {
+            comparison.left.insert(left.into());
+            comparison.right.insert(right.into());
+        }
+        comparison
+    }
+
+    // Total distance between the lists after sorting both and pairing them up by rank. Every
+    // distance fits in a u64 and there are at most u64::MAX pairs, so the total fits in a u128.
+    pub fn sorted_distance(&self) -> Result<u128, LengthMismatch> {
+        if self.left.len() != self.right.len() {
+            return Err(LengthMismatch {
+                left: self.left.len(),
+                right: self.right.len(),
+            });
+        }
+
+        let mut left = self.left.iter();
+        let mut right = self.right.iter();
+        let (mut a, mut b) = (left.next(), right.next());
+        let mut distance = 0;
+
+        while let (Some((x, x_count)), Some((y, y_count))) = (a, b) {
+            let pairs = x_count.min(y_count);
+            distance += x.abs_diff(y) as u128 * pairs as u128;
+            a = if x_count > pairs {
+                Some((x, x_count - pairs))
+            } else {
+                left.next()
+            };
+            b = if y_count > pairs {
+                Some((y, y_count - pairs))
+            } else {
+                right.next()
+            };
+        }
+
+        Ok(distance)
+    }
+
+    // Sum of every left value multiplied by how often it appears on the right. The score is exact
+    // whenever it fits in an i128, which always holds while both lists have fewer than 2^32
+    // entries; beyond that a single value can already overflow, which is reported as an error.
+    pub fn similarity(&self) -> Result<i128, SimilarityOverflow> {
+        self.left.iter().try_fold(0i128, |total, (value, count)| {
+            let occurrences = (count as u128).checked_mul(self.right.count(value) as u128);
+            occurrences
+                .and_then(|occurrences| i128::try_from(occurrences).ok())
+                .and_then(|occurrences| occurrences.checked_mul(value as i128))
+                .and_then(|term| total.checked_add(term))
+                .ok_or(SimilarityOverflow)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comparison() {
+        let comparison =
+            ListComparison::from_pairs([(3, 4), (4, 3), (2, 5), (1, 3), (3, 9), (3, 3)]);
+        assert_eq!(comparison.sorted_distance(), Ok(11));
+        assert_eq!(comparison.similarity(), Ok(31));
+
+        let comparison = ListComparison::from_streams([-5i64, 10], [5i32, -10]);
+        assert_eq!(comparison.sorted_distance(), Ok(10));
+        assert_eq!(comparison.similarity(), Ok(0));
+    }
+
+    #[test]
+    fn test_length_mismatch() {
+        let comparison = ListComparison::from_streams([1, 2, 3], [1, 2]);
+        assert_eq!(
+            comparison.sorted_distance(),
+            Err(LengthMismatch { left: 3, right: 2 })
+        );
+        assert_eq!(comparison.similarity(), Ok(3));
+    }
+
+    #[test]
+    fn test_large_tables() {
+        let mut comparison = ListComparison::default();
+        for _ in 0..3 {
+            comparison.left.insert(i64::MIN);
+            comparison.right.insert(i64::MAX);
+        }
+        assert_eq!(comparison.sorted_distance(), Ok(3 * u64::MAX as u128));
+
+        let comparison = ListComparison::from_streams([i64::MAX; 3], [i64::MAX; 4]);
+        assert_eq!(comparison.similarity(), Ok(12 * i64::MAX as i128));
+    }
+
+    #[test]
+    fn test_similarity_overflow() {
+        let mut comparison = ListComparison::default();
+        comparison.left.counts.insert(i64::MAX, u64::MAX);
+        comparison.right.counts.insert(i64::MAX, u64::MAX);
+        assert_eq!(comparison.similarity(), Err(SimilarityOverflow));
+
+        comparison.left.counts.insert(i64::MAX, 1 << 32);
+        comparison.right.counts.insert(i64::MAX, 1 << 33);
+        assert_eq!(comparison.similarity(), Err(SimilarityOverflow));
+
+        comparison.right.counts.insert(i64::MAX, 1 << 32);
+        assert_eq!(comparison.similarity(), Ok(i64::MAX as i128 * (1 << 64)));
+        comparison.left.counts.insert(i64::MAX - 1, 1 << 32);
+        comparison.right.counts.insert(i64::MAX - 1, 1 << 32);
+        assert_eq!(comparison.similarity(), Err(SimilarityOverflow));
+    }
+
+    #[test]
+    fn test_rank_statistics() {
+        let table = [3, 4, 2, 1, 3, 3].into_iter().collect::<FrequencyTable>();
+        assert_eq!(table.len(), 6);
+        assert_eq!(table.count(3), 3);
+        assert_eq!((table.min(), table.max()), (Some(1), Some(4)));
+        assert_eq!(table.rank(3), 2);
+        assert_eq!(table.nth(5), Some(4));
+        assert_eq!(table.nth(6), None);
+        assert_eq!(table.median(), Some(3));
+        assert_eq!(table.quantile(0.0), Some(1));
+        assert_eq!(table.quantile(0.25), Some(2));
+        assert_eq!(table.quantile(1.0), Some(4));
+        assert_eq!(FrequencyTable::default().median(), None);
+    }
+}
@@ -0,0 +1,126 @@
+// Dijkstra's algorithm driven one node at a time, so callers can decide when to stop and
+// simulations can draw the frontier in between.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    hash::Hash,
+};
+
+struct Entry<N> {
+    cost: u64,
+    node: N,
+}
+
+// Reversed on cost so the max-heap pops the cheapest entry first.
+impl<N> Ord for Entry<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.cmp(&self.cost)
+    }
+}
+
+impl<N> PartialOrd for Entry<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> PartialEq for Entry<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl<N> Eq for Entry<N> {}
+
+pub struct Search<N> {
+    heap: BinaryHeap<Entry<N>>,
+    dist: HashMap<N, u64>,
+    current: Option<N>,
+}
+
+impl<N: Copy + Eq + Hash> Search<N> {
+    pub fn new(start: N) -> Self {
+        Search {
+            heap: BinaryHeap::from([Entry {
+                cost: 0,
+                node: start,
+            }]),
+            dist: HashMap::from([(start, 0)]),
+            current: None,
+        }
+    }
+
+    // The cheapest node that has not been settled yet, with its final distance. Entries that were
+    // superseded by a cheaper path are skipped.
+    pub fn pop(&mut self) -> Option<(N, u64)> {
+        while let Some(Entry { cost, node }) = self.heap.pop() {
+            if cost <= self.dist[&node] {
+                self.current = Some(node);
+                return Some((node, cost));
+            }
+        }
+        None
+    }
+
+    // Records a path of the given cost to the node if it is shorter than the known one.
+    pub fn relax(&mut self, node: N, cost: u64) -> bool {
+        if self.distance(node).is_some_and(|known| known <= cost) {
+            return false;
+        }
+        self.dist.insert(node, cost);
+        self.heap.push(Entry { cost, node });
+        true
+    }
+
+    pub fn distance(&self, node: N) -> Option<u64> {
+        self.dist.get(&node).copied()
+    }
+
+    pub fn distances(&self) -> &HashMap<N, u64> {
+        &self.dist
+    }
+
+    pub fn into_distances(self) -> HashMap<N, u64> {
+        self.dist
+    }
+
+    // The node popped last.
+    pub fn current(&self) -> Option<N> {
+        self.current
+    }
+
+    // Nodes waiting on the heap, including superseded entries.
+    pub fn frontier(&self) -> impl Iterator<Item = N> + '_ {
+        self.heap.iter().map(|entry| entry.node)
+    }
+
+    pub fn frontier_len(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let edges = HashMap::from([('a', vec![('b', 4), ('c', 1)]), ('c', vec![('b', 2)])]);
+        let mut search = Search::new('a');
+        let mut order = Vec::new();
+        while let Some((node, cost)) = search.pop() {
+            order.push((node, cost));
+            for &(next, weight) in edges.get(&node).into_iter().flatten() {
+                search.relax(next, cost + weight);
+            }
+        }
+
+        assert_eq!(order, vec![('a', 0), ('c', 1), ('b', 3)]);
+        assert_eq!(search.distance('b'), Some(3));
+        assert_eq!(search.distance('d'), None);
+        assert_eq!(search.current(), Some('b'));
+        assert!(!search.relax('b', 3));
+        assert_eq!(search.frontier_len(), 0);
+    }
+}
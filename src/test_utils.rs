@@ -0,0 +1,19 @@
+// Helpers shared by the test modules.
+
+// A small linear congruential generator, so randomised tests are reproducible without a crate.
+pub struct Lcg(u64);
+
+impl Lcg {
+    pub fn new(seed: u64) -> Self {
+        Lcg(seed)
+    }
+
+    // A number in 0..max.
+    pub fn below(&mut self, max: u64) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) % max
+    }
+}
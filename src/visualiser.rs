@@ -0,0 +1,310 @@
+// Step-by-step terminal playback for grid simulations, drawn with plain ANSI escapes.
+
+use std::{
+    io::{self, Read, Write},
+    process::{Command as Process, Stdio},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use crate::{day14, day15, day16, day18, day6};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub rows: Vec<String>,
+    pub status: String,
+}
+
+impl Frame {
+    pub fn from_grid<T>(grid: &[Vec<T>], cell: impl Fn(&T) -> char, status: String) -> Self {
+        Frame {
+            rows: grid
+                .iter()
+                .map(|row| row.iter().map(&cell).collect())
+                .collect(),
+            status,
+        }
+    }
+}
+
+pub trait Simulation {
+    fn frame(&self) -> Frame;
+
+    // Advances by one step. Returns false once there is nothing left to simulate.
+    fn step(&mut self) -> bool;
+}
+
+pub fn simulation(name: &str, input: &str) -> Option<Box<dyn Simulation>> {
+    Some(match name {
+        "day6" => Box::new(day6::simulation(input)),
+        "day14" => Box::new(day14::simulation(input)),
+        "day15" => Box::new(day15::simulation(input)),
+        "day15-wide" => Box::new(day15::wide_simulation(input)),
+        "day16" => Box::new(day16::simulation(input)),
+        "day18" => Box::new(day18::simulation(input)),
+        _ => return None,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    TogglePlay,
+    Step,
+    Faster,
+    Slower,
+    Quit,
+}
+
+impl Command {
+    pub fn from_key(key: u8) -> Option<Self> {
+        match key {
+            b' ' | b'p' => Some(Command::TogglePlay),
+            b'n' | b'.' => Some(Command::Step),
+            b'+' | b'=' => Some(Command::Faster),
+            b'-' => Some(Command::Slower),
+            // Not ESC: it also starts the sequences sent by arrow and function keys.
+            b'q' | 0x03 => Some(Command::Quit),
+            _ => None,
+        }
+    }
+}
+
+const MIN_DELAY: Duration = Duration::from_millis(5);
+const MAX_DELAY: Duration = Duration::from_millis(2000);
+
+pub struct Player<S: Simulation + ?Sized> {
+    simulation: Box<S>,
+    playing: bool,
+    finished: bool,
+    delay: Duration,
+    steps: usize,
+}
+
+impl<S: Simulation + ?Sized> Player<S> {
+    pub fn new(simulation: Box<S>) -> Self {
+        Player {
+            simulation,
+            playing: false,
+            finished: false,
+            delay: Duration::from_millis(100),
+            steps: 0,
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    pub fn step(&mut self) {
+        if self.finished {
+            self.playing = false;
+            return;
+        }
+        if self.simulation.step() {
+            self.steps += 1;
+        } else {
+            self.finished = true;
+            self.playing = false;
+        }
+    }
+
+    // Applies a command and returns false if the player should quit.
+    pub fn handle(&mut self, command: Command) -> bool {
+        match command {
+            Command::TogglePlay => self.playing = !self.playing && !self.finished,
+            Command::Step => {
+                self.playing = false;
+                self.step();
+            }
+            Command::Faster => self.delay = (self.delay / 2).max(MIN_DELAY),
+            Command::Slower => self.delay = (self.delay * 2).min(MAX_DELAY),
+            Command::Quit => return false,
+        }
+        true
+    }
+
+    pub fn render(&self) -> String {
+        let frame = self.simulation.frame();
+        let state = match (self.finished, self.playing) {
+            (true, _) => "finished",
+            (false, true) => "playing",
+            (false, false) => "paused",
+        };
+
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        for row in &frame.rows {
+            screen.push_str(row);
+            screen.push_str("\r\n");
+        }
+        screen.push_str(&format!(
+            "\r\n{}\r\nstep {} | {} | {} ms/step\r\n[space] play/pause  [n] step  [+/-] speed  [q] quit\r\n",
+            frame.status,
+            self.steps,
+            state,
+            self.delay.as_millis()
+        ));
+        screen
+    }
+}
+
+// Puts the terminal into raw mode for as long as it is alive, then restores the settings it had
+// before and shows the cursor again.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        Ok(RawMode {
+            saved: saved.trim().to_string(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "\x1b[?25h\r\n");
+        let _ = stdout.flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Process::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "stty {} failed: {}",
+            args.join(" "),
+            output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub fn play(simulation: Box<dyn Simulation>) -> io::Result<()> {
+    let mut player = Player::new(simulation);
+    let mut stdout = io::stdout();
+
+    let _raw_mode = RawMode::enable()?;
+    write!(stdout, "\x1b[?25l")?;
+
+    let (keys, commands) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            let Ok(byte) = byte else {
+                break;
+            };
+            if let Some(command) = Command::from_key(byte) {
+                if keys.send(command).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    loop {
+        write!(stdout, "{}", player.render())?;
+        stdout.flush()?;
+
+        let command = if player.is_playing() {
+            match commands.recv_timeout(player.delay()) {
+                Ok(command) => Some(command),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => Some(Command::Quit),
+            }
+        } else {
+            Some(commands.recv().unwrap_or(Command::Quit))
+        };
+
+        match command {
+            Some(command) => {
+                if !player.handle(command) {
+                    break;
+                }
+            }
+            None => player.step(),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Counter {
+        value: usize,
+        limit: usize,
+    }
+
+    impl Simulation for Counter {
+        fn frame(&self) -> Frame {
+            Frame {
+                rows: vec!["#".repeat(self.value)],
+                status: format!("value {}", self.value),
+            }
+        }
+
+        fn step(&mut self) -> bool {
+            if self.value == self.limit {
+                return false;
+            }
+            self.value += 1;
+            true
+        }
+    }
+
+    #[test]
+    fn test_player_controls() {
+        let mut player = Player::new(Box::new(Counter { value: 0, limit: 2 }));
+        assert!(!player.is_playing());
+
+        assert!(player.handle(Command::TogglePlay));
+        assert!(player.is_playing());
+        assert!(player.handle(Command::Step));
+        assert!(!player.is_playing());
+
+        assert!(player.handle(Command::Faster));
+        assert_eq!(player.delay(), Duration::from_millis(50));
+        for _ in 0..20 {
+            player.handle(Command::Slower);
+        }
+        assert_eq!(player.delay(), MAX_DELAY);
+
+        player.handle(Command::TogglePlay);
+        player.step();
+        player.step();
+        assert!(!player.is_playing());
+        player.handle(Command::TogglePlay);
+        assert!(!player.is_playing());
+        assert!(player.render().contains("step 2 | finished"));
+        assert!(player.render().contains("##\r\n\r\nvalue 2"));
+
+        assert!(!player.handle(Command::Quit));
+    }
+
+    #[test]
+    fn test_keys() {
+        assert_eq!(Command::from_key(b' '), Some(Command::TogglePlay));
+        assert_eq!(Command::from_key(b'n'), Some(Command::Step));
+        assert_eq!(Command::from_key(b'q'), Some(Command::Quit));
+        assert_eq!(Command::from_key(b'x'), None);
+        assert_eq!(Command::from_key(0x03), Some(Command::Quit));
+        assert!(b"\x1b[A\x1b[B\x1b[C\x1b[D"
+            .iter()
+            .all(|&key| Command::from_key(key).is_none()));
+    }
+}